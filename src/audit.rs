@@ -0,0 +1,211 @@
+//! # audit.rs
+//!
+//! Checks on the dates of the indices of each basename, to catch the
+//! days on which logstash failed to write an index, indices dated in the
+//! future, and several versioned indices covering the same day.
+use chrono::naive::NaiveDate;
+use crate::index::{Granularity, Index};
+use crate::report::{gaps, Gap};
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// A problem with the dates of a basename's indices
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Finding {
+    /// No index of the basename, or of any version of it, covers these days,
+    /// which lie between the oldest such index and yesterday
+    Missing { basename: String, from: NaiveDate, to: NaiveDate, days: i64 },
+    /// The index covers only days after today
+    Future { basename: String, index: String, date: NaiveDate },
+    /// Several indices of the same basename, or of differently versioned
+    /// basenames (eg `foo-1.2.3` and `foo`), cover the same period
+    Duplicate { basename: String, date: NaiveDate, indices: Vec<String> },
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Finding::Missing { basename, from, to, days } => {
+                let gap = Gap { from: *from, to: *to };
+                write!(f, "{}: missing {} ({} days)", basename, gap, days)
+            },
+            Finding::Future { basename, index, date } =>
+                write!(f, "{}: {} is dated in the future ({})", basename, index, date),
+            Finding::Duplicate { basename, date, indices } =>
+                write!(f, "{}: {} indices for {}: {}", basename, indices.len(), date, indices.join(",")),
+        }
+    }
+}
+
+// A basename ending in a dotted version number
+fn versioned() -> Regex {
+    Regex::new(r"^(.+)-\d+(\.\d+)+$").expect("version regex is valid")
+}
+
+// The basename with any trailing version (eg the -6.4.2 of winlogbeat-6.4.2)
+// removed, so that the indices of each version of a source may be compared
+fn family<'a>(version: &Regex, basename: &'a str) -> &'a str {
+    match version.captures(basename).and_then(|c| c.get(1)) {
+        Some(m) => &basename[..m.end()],
+        None => basename,
+    }
+}
+
+/// Audit the dates of a set of indices. The indices of each basename, taken
+/// together with those of any other version of it, are checked for days
+/// missing between the oldest of them and yesterday, since today's index may
+/// not have been written to yet; a basename retired by a version bump is thus
+/// carried on by its successor. Indices are also checked for dates after
+/// today, and those whose basenames differ only by a version for duplicates
+/// covering the same period. Rollover indices carry no date
+/// of their own, so they are left out of all but the future check.
+///
+/// # Parameters
+///
+/// * `indices` - The indices to audit, in any order
+/// * `today`   - The day against which missing and future dates are judged
+///
+/// # Returns
+///
+/// * `Vector` of `Finding`s: missing days by basename, then future dated
+///   indices, then duplicates
+pub fn audit(indices: &[Index], today: NaiveDate) -> Vec<Finding> {
+    let version = versioned();
+    let mut by_family: BTreeMap<&str, Vec<Index>> = BTreeMap::new();
+    for idx in indices {
+        by_family.entry(family(&version, idx.name())).or_default().push(idx.clone());
+    }
+
+    let mut findings = Vec::new();
+    for (name, indices) in &by_family {
+        // count today as covered, so that a basename which has stopped being
+        // written to shows up as missing its latest days
+        let mut dated = indices.iter()
+            .filter(|idx| !matches!(idx.granularity, Granularity::Rollover(_)))
+            .filter(|idx| idx.date < today)
+            .cloned()
+            .collect::<Vec<Index>>();
+        if !dated.is_empty() {
+            dated.push(Index::from_period(*name, today, Granularity::Daily));
+        }
+        findings.extend(gaps(&dated).into_iter().map(|gap| Finding::Missing {
+            basename: name.to_string(),
+            from: gap.from,
+            to: gap.to,
+            days: gap.days(),
+        }));
+    }
+
+    let mut sorted = indices.to_vec();
+    sorted.sort_unstable();
+    findings.extend(sorted.iter()
+        .filter(|idx| idx.date > today)
+        .map(|idx| Finding::Future {
+            basename: idx.name().to_string(),
+            index: idx.to_string(),
+            date: idx.date,
+        }));
+
+    let mut by_period: BTreeMap<(&str, NaiveDate, Granularity), Vec<String>> = BTreeMap::new();
+    for idx in &sorted {
+        if let Granularity::Rollover(_) = idx.granularity {
+            continue;
+        }
+        by_period.entry((family(&version, idx.name()), idx.date, idx.granularity))
+            .or_default()
+            .push(idx.to_string());
+    }
+    findings.extend(by_period.into_iter()
+        .filter(|(_, indices)| indices.len() > 1)
+        .map(|((basename, date, _), indices)| Finding::Duplicate {
+            basename: basename.to_string(),
+            date,
+            indices,
+        }));
+
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2018, 10, d).unwrap()
+    }
+
+    #[test]
+    fn strips_versions_from_basenames() {
+        let version = versioned();
+        assert_eq!(family(&version, "winlogbeat-6.4.2"), "winlogbeat");
+        assert_eq!(family(&version, "foo-1.2.3"), "foo");
+        assert_eq!(family(&version, "app-logs"), "app-logs");
+        assert_eq!(family(&version, "app-2"), "app-2");
+    }
+
+    #[test]
+    fn finds_missing_days() {
+        let indices = vec![
+            Index::new("logs", 2018, 10, 1),
+            Index::new("logs", 2018, 10, 3),
+            Index::new("logs", 2018, 10, 4),
+        ];
+        let findings = audit(&indices, day(5));
+        assert_eq!(findings, vec![Finding::Missing { basename: "logs".into(), from: day(2), to: day(2), days: 1 }]);
+        assert_eq!(findings[0].to_string(), "logs: missing 2018-10-02 (1 days)");
+    }
+
+    #[test]
+    fn finds_a_basename_which_stopped() {
+        let indices = vec![Index::new("logs", 2018, 10, 1), Index::new("logs", 2018, 10, 2)];
+        assert_eq!(audit(&indices, day(6)),
+            vec![Finding::Missing { basename: "logs".into(), from: day(3), to: day(5), days: 3 }]);
+        // today's index may not exist yet
+        assert!(audit(&indices, day(3)).is_empty());
+    }
+
+    #[test]
+    fn a_new_version_carries_on_from_a_retired_one() {
+        let indices = vec![
+            Index::new("winlogbeat-6.4.2", 2018, 10, 1),
+            Index::new("winlogbeat-6.4.2", 2018, 10, 2),
+            Index::new("winlogbeat-6.5.0", 2018, 10, 3),
+            Index::new("winlogbeat-6.5.0", 2018, 10, 4),
+        ];
+        assert!(audit(&indices, day(5)).is_empty());
+
+        // but a gap between the versions is still missing
+        let indices = vec![Index::new("winlogbeat-6.4.2", 2018, 10, 1), Index::new("winlogbeat-6.5.0", 2018, 10, 4)];
+        assert_eq!(audit(&indices, day(5)),
+            vec![Finding::Missing { basename: "winlogbeat".into(), from: day(2), to: day(3), days: 2 }]);
+    }
+
+    #[test]
+    fn finds_future_indices() {
+        let indices = vec![Index::new("logs", 2018, 10, 4), Index::new("logs", 2018, 10, 9)];
+        let findings = audit(&indices, day(5));
+        assert_eq!(findings, vec![Finding::Future {
+            basename: "logs".into(),
+            index: "logs-2018.10.09".into(),
+            date: day(9),
+        }]);
+    }
+
+    #[test]
+    fn finds_versioned_duplicates() {
+        let indices = vec![
+            Index::new("foo", 2018, 10, 4),
+            Index::new("foo-1.2.3", 2018, 10, 4),
+            Index::new("foo-1.2.3", 2018, 10, 3),
+            Index::new("foo", 2018, 10, 3),
+        ];
+        let findings = audit(&indices, day(5));
+        let duplicates = findings.iter()
+            .filter(|f| matches!(f, Finding::Duplicate { .. }))
+            .collect::<Vec<&Finding>>();
+        assert_eq!(duplicates.len(), 2);
+        assert_eq!(duplicates[0].to_string(), "foo: 2 indices for 2018-10-03: foo-2018.10.03,foo-1.2.3-2018.10.03");
+    }
+}
@@ -0,0 +1,177 @@
+//! # clock.rs
+//!
+//! The source of the current date for age calculations. CmdProcessor asks
+//! its Clock for the time rather than reading the system clock, so that
+//! retention decisions may be simulated as of another date, and tested.
+//! The Timezone decides which day it is at that time.
+use chrono::{
+    naive::{NaiveDate, NaiveDateTime},
+    DateTime,
+    FixedOffset,
+    Local,
+    TimeZone,
+    Utc,
+};
+use crate::errors::EcError;
+use std::{fmt, str::FromStr};
+
+/// Supplies the current instant. `CmdProcessor::today` turns it into the
+/// date index ages are measured against.
+pub trait Clock {
+    /// The current instant
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// Reads the system clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Always reports the same instant
+#[derive(Debug, Clone, Copy)]
+pub struct FixedClock {
+    now: DateTime<Utc>,
+}
+
+impl FixedClock {
+    /// New up a FixedClock reporting the supplied instant
+    ///
+    /// # Parameters
+    ///
+    /// * `now` - The instant to report
+    ///
+    /// # Returns
+    ///
+    /// * `FixedClock` instance
+    pub fn new(now: DateTime<Utc>) -> FixedClock {
+        FixedClock { now }
+    }
+
+    /// New up a FixedClock reporting the start of a day, in UTC
+    ///
+    /// # Parameters
+    ///
+    /// * `date` - The day to report
+    ///
+    /// # Returns
+    ///
+    /// * `FixedClock` instance
+    pub fn on(date: NaiveDate) -> FixedClock {
+        FixedClock::new(DateTime::from_naive_utc_and_offset(date.and_time(Default::default()), Utc))
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.now
+    }
+}
+
+/// The timezone in which index dates are reckoned. Logstash names indices
+/// after the UTC day, hence the default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Timezone {
+    #[default]
+    Utc,
+    /// The timezone of the machine elasticlean runs on
+    Local,
+    /// A fixed offset from UTC, eg `+05:30`
+    Fixed(FixedOffset),
+}
+
+impl Timezone {
+    /// The date at an instant, in this timezone
+    ///
+    /// # Parameters
+    ///
+    /// * `now` - The instant
+    ///
+    /// # Returns
+    ///
+    /// * `NaiveDate` instance
+    pub fn date_of(&self, now: DateTime<Utc>) -> NaiveDate {
+        match self {
+            Timezone::Utc => now.date_naive(),
+            Timezone::Local => now.with_timezone(&Local).date_naive(),
+            Timezone::Fixed(offset) => now.with_timezone(offset).date_naive(),
+        }
+    }
+
+    /// The instant at which a day starts, in this timezone
+    ///
+    /// # Parameters
+    ///
+    /// * `date` - The day
+    ///
+    /// # Returns
+    ///
+    /// * `DateTime<Utc>` instance
+    pub fn start_of(&self, date: NaiveDate) -> DateTime<Utc> {
+        let midnight: NaiveDateTime = date.and_time(Default::default());
+        let local = match self {
+            Timezone::Utc => None,
+            Timezone::Local => Local.from_local_datetime(&midnight).earliest().map(|d| d.with_timezone(&Utc)),
+            Timezone::Fixed(offset) => offset.from_local_datetime(&midnight).single().map(|d| d.with_timezone(&Utc)),
+        };
+        local.unwrap_or_else(|| Utc.from_utc_datetime(&midnight))
+    }
+}
+
+impl FromStr for Timezone {
+    type Err = EcError;
+
+    fn from_str(input: &str) -> Result<Timezone, EcError> {
+        match input.trim() {
+            "utc" | "UTC" | "Z" => Ok(Timezone::Utc),
+            "local" | "Local" => Ok(Timezone::Local),
+            offset => offset.parse::<FixedOffset>()
+                .map(Timezone::Fixed)
+                .map_err(|e| EcError::ParseError(
+                    format!("timezone {}: expected UTC, local, or an offset like +05:30 ({})", input, e))),
+        }
+    }
+}
+
+impl fmt::Display for Timezone {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Timezone::Utc => write!(f, "UTC"),
+            Timezone::Local => write!(f, "local"),
+            Timezone::Fixed(offset) => write!(f, "{}", offset),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_timezones() {
+        assert_eq!("UTC".parse::<Timezone>().unwrap(), Timezone::Utc);
+        assert_eq!("local".parse::<Timezone>().unwrap(), Timezone::Local);
+        assert_eq!("+05:30".parse::<Timezone>().unwrap(), Timezone::Fixed(FixedOffset::east_opt(5 * 3600 + 1800).unwrap()));
+        assert!("Mars/Olympus".parse::<Timezone>().is_err());
+    }
+
+    #[test]
+    fn fixed_offset_shifts_the_date() {
+        let now = Utc.with_ymd_and_hms(2018, 10, 2, 22, 30, 0).unwrap();
+        let east = Timezone::Fixed(FixedOffset::east_opt(2 * 3600).unwrap());
+        assert_eq!(Timezone::Utc.date_of(now), NaiveDate::from_ymd_opt(2018, 10, 2).unwrap());
+        assert_eq!(east.date_of(now), NaiveDate::from_ymd_opt(2018, 10, 3).unwrap());
+        let date = NaiveDate::from_ymd_opt(2018, 10, 3).unwrap();
+        assert_eq!(east.start_of(date), Utc.with_ymd_and_hms(2018, 10, 2, 22, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn fixed_clock_reports_its_date() {
+        let date = NaiveDate::from_ymd_opt(2018, 10, 2).unwrap();
+        assert_eq!(FixedClock::on(date).now().date_naive(), date);
+    }
+}
@@ -2,18 +2,41 @@
 //!
 //! provides a CmdProcessor struct which handles interfacing with
 //! elasticsearch, exposing commands to perform various tasks related to cleanup.
-use crate::constants;
+use crate::audit::{audit, Finding};
+use crate::clock::{Clock, SystemClock};
+use crate::constants::{REINDEX_POLL_SECS, REINDEX_TIMEOUT_SECS, SNAPSHOT_POLL_SECS, SNAPSHOT_TIMEOUT_SECS};
 use crate::elasticrud::Elasticrud;
+use crate::daterange::DateRange;
 use crate::errors::EcError;
-use crate::index::Index;
-use std::collections::HashSet;
+use crate::holds::{Hold, HoldStore};
+use crate::index::{Granularity, Index};
+use crate::rawindex::RawIndex;
+use crate::rawsnapshot::RawSnapshot;
+use crate::report::BasenameSummary;
+use chrono::{naive::NaiveDate, Datelike};
+use std::cell::RefCell;
+use std::cmp;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::{thread, time::Duration};
 use crate::traits::ElasticIndex;
-use crate::config::Config;
+use crate::config::{Config, Policy};
+use crate::namefilter::NameFilter;
+use crate::pattern::IndexMatcher;
+use crate::rawalias::{write_indices, RawAlias};
+use crate::rawdatastream::RawDataStream;
 
 /// Struct responsible for executing commands
 pub struct CmdProcessor<'a, 'b: 'a> {
     pub config: &'b Config,
     pub ec: Elasticrud<'a>,
+    /// Parses index names using the config's naming patterns
+    pub matcher: IndexMatcher,
+    /// Supplies the date index ages are measured against
+    pub clock: Box<dyn Clock>,
+    // The aliases, once they have been looked up
+    aliases: RefCell<Option<Vec<RawAlias>>>,
+    // The write indices, once they have been looked up
+    protected: RefCell<Option<HashSet<String>>>,
 }
 
 impl<'a, 'b> CmdProcessor<'a, 'b> {
@@ -21,50 +44,118 @@ impl<'a, 'b> CmdProcessor<'a, 'b> {
     ///
     /// # Arguments
     ///
-    /// * `host` - A non owned host name
-    /// * `port` - The port number
+    /// * `config` - The Config, which says how to reach elasticsearch
     ///
     /// # Returns
     ///
-    /// * `CmdProcessor` instance
-    pub fn new(config: &'b Config) -> CmdProcessor<'a, 'b>
+    /// * `CmdProcessor` instance if successful
+    /// * `EcError` if the connection's certificates cannot be loaded
+    pub fn new(config: &'b Config) -> Result<CmdProcessor<'a, 'b>, EcError>
     {
-        let port  = config.port;
-       CmdProcessor {
+        CmdProcessor::with_clock(config, Box::new(SystemClock))
+    }
+
+    /// new up a CmdProcessor which measures index ages against the supplied
+    /// clock rather than the system clock, eg to simulate what a policy
+    /// would delete on a given date.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The Config
+    /// * `clock`  - The source of today's date
+    ///
+    /// # Returns
+    ///
+    /// * `CmdProcessor` instance if successful
+    /// * `EcError` if the connection's certificates cannot be loaded
+    pub fn with_clock(config: &'b Config, clock: Box<dyn Clock>) -> Result<CmdProcessor<'a, 'b>, EcError>
+    {
+       Ok(CmdProcessor {
            config: config,
-        ec: Elasticrud::new(&config.host, port)
-       }
+        ec: Elasticrud::new(&config.urls, &config.connection, config.sniff)?,
+        matcher: IndexMatcher::new(config),
+        clock,
+        aliases: RefCell::new(None),
+        protected: RefCell::new(None),
+       })
+    }
+
+    /// The date index ages are measured against: the date, in the config's
+    /// timezone, `grace_hours` before the processor's clock. An index thus
+    /// only turns a day old once its period ended at least `grace_hours` ago,
+    /// so that indices logstash may still be writing to are left alone.
+    ///
+    /// # Returns
+    ///
+    /// * `NaiveDate` instance
+    pub fn today(&self) -> NaiveDate {
+        let now = self.clock.now() - chrono::Duration::hours(self.config.grace_hours.into());
+        self.config.timezone.date_of(now)
     }
 
-    /// Given optional name, start, and end, return a Result wrapped
+    /// The number of days old an index is, as of `today`. Every retention
+    /// decision is based upon this age.
+    ///
+    /// # Arguments
+    ///
+    /// * `idx` - The index
+    ///
+    /// # Returns
+    ///
+    /// * `i64` number of days
+    pub fn age(&self, idx: &Index) -> i64 {
+        idx.days_since(&self.today())
+    }
+
+    /// Given an optional name filter and a date range, return a Result wrapped
     /// vector of Index structs if successful, or an EcError in the failure case.
-    /// The start and end are expressed in age in days from today, and can
-    /// express the interval [s,e).
+    /// The range may hold absolute dates, or dates relative to today (see
+    /// `DateRange::from_offsets` for the cli's start and end offsets).
     ///
     /// # Arguments
     ///
-    /// * `name`  - An optional index name
-    /// * `start` - An optional start offset, from today
-    /// * `end`   - An optional end offset, from today
+    /// * `name`  - An optional filter on the base name of the index
+    /// * `range` - The dates the indices must lie within
     ///
     /// # Returns
     ///
     /// * Vector of `Index` instances, on Success
     /// * `ExError` on Failure
-    pub fn get_indices(&self, name: Option<String>, start: Option<i32>, end: Option<i32>)
+    pub fn get_indices(&self, name: Option<NameFilter>, range: &DateRange)
     -> Result<Vec<Index>, EcError> {
 
+        let r = self.get_raw_indices(name, range)?
+                .into_iter()
+                .map(|(idx, _)| idx);
+
+        Ok(r.collect::<Vec<Index>>())
+    }
+
+    /// Same as `get_indices`, but pairs each `Index` with the `RawIndex`
+    /// metadata (health, sizes, etc) elasticsearch returned for it.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`  - An optional filter on the base name of the index
+    /// * `range` - The dates the indices must lie within
+    ///
+    /// # Returns
+    ///
+    /// * Vector of `(Index, RawIndex)` tuples, on Success
+    /// * `ExError` on Failure
+    pub fn get_raw_indices(&self, name: Option<NameFilter>, range: &DateRange)
+    -> Result<Vec<(Index, RawIndex)>, EcError> {
+
+        // indices named like neither a pattern nor a rollover series are
+        // still rollover indices if an alias points at them
+        let aliased = self.aliases()?.into_iter().map(|a| a.index).collect::<HashSet<String>>();
         let  r = self.ec.get_raw_indices()?.into_iter();
         // filter_map performs transformation, keeping any successful Result
-        let  r = r.filter_map(|v| Index::from_str(v.index.as_str()).ok());
-        // if name is supplied match against it
-        let r = r.filter(|v| if let Some(ref n) = name { v.name == *n } else {true});
-        // if start is supplied filter out any index that is loder than supplied start
-        let r = r.filter(|v| if let Some(n) = start {v.days() <= n.into() } else {true});
-        // if end is supplied, filter out any index which is newer than end
-        let r = r.filter(|v| if let Some(n) = end {v.days() > n.into() } else {true});
+        let  r = r.filter_map(|v| self.matcher.parse_raw(&v, &aliased).ok().map(|idx| (idx, v)));
+        let today = self.today();
+        let r = r.filter(|(v, _)| matches(v, &name, range, today));
 
-        Ok(r.collect::<Vec<Index>>())
+        Ok(r.collect::<Vec<(Index, RawIndex)>>())
     }
 
 
@@ -72,53 +163,103 @@ impl<'a, 'b> CmdProcessor<'a, 'b> {
     ///
     /// # Arguments
     ///
-    /// * `name`  - Optional index name
-    /// * `start` - Optional starting offset, from today
-    /// * `end`   - Optional ending offset, from today
+    /// * `name`  - Optional filter on the base name of the index
+    /// * `range` - The dates the indices must lie within
     ///
     /// # Returns
     ///
-    /// * Vector of `String` upon success
+    /// * Vector of `String`, sorted, upon success
     /// * EcError upon failure
-    pub fn query_names(&self, name: Option<String>, start: Option<i32>, end: Option<i32>)
+    pub fn query_names(&self, name: Option<NameFilter>, range: &DateRange)
     -> Result<Vec<String>, EcError> {
 
-        let  results: Vec<Index> = self.get_indices(name, start, end)?;
+        let  results: Vec<Index> = self.get_indices(name, range)?;
 
-        let mut seen: HashSet<String> = HashSet::new();
+        let seen: BTreeSet<String> = results.iter()
+            .map(|r| r.name().to_string())
+            .collect();
 
-        for r in &results {
-            if !seen.contains(r.name()) {
-                let n = r.name().to_string();
-                seen.insert(n);
-            }
+        Ok(seen.into_iter().collect::<Vec<String>>())
+    }
+
+    /// Roll up the indices matching criteria by basename: how many there are,
+    /// the dates they span and any gaps in them, their total size, and their
+    /// worst health.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`  - Optional filter on the base name of the index
+    /// * `range` - The dates the indices must lie within
+    ///
+    /// # Returns
+    ///
+    /// * Vector of `BasenameSummary`s, sorted by basename, upon success
+    /// * EcError upon failure
+    pub fn summarize(&self, name: Option<NameFilter>, range: &DateRange)
+    -> Result<Vec<BasenameSummary>, EcError> {
+
+        let mut by_name: BTreeMap<String, Vec<(Index, RawIndex)>> = BTreeMap::new();
+        for (idx, raw) in self.get_raw_indices(name, range)? {
+            by_name.entry(idx.name.clone()).or_default().push((idx, raw));
         }
 
-        let return_results = seen.drain().collect::<Vec<String>>();
-        return Ok(return_results)
+        by_name.iter()
+            .map(|(name, indices)| BasenameSummary::of(name, indices))
+            .collect()
     }
 
 
+    /// Audit the dates of the indices matching criteria, reporting days on
+    /// which a basename has no index, indices dated after today, and several
+    /// indices, perhaps of differently versioned basenames, covering the same
+    /// period. See `audit::audit`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Optional filter on the base name of the index
+    ///
+    /// # Returns
+    ///
+    /// * Vector of `Finding`s upon success
+    /// * EcError upon failure
+    pub fn audit(&self, name: Option<NameFilter>) -> Result<Vec<Finding>, EcError> {
+        let indices = self.get_indices(name, &DateRange::all())?;
+        Ok(audit(&indices, self.today()))
+    }
+
+    /// Retrieve the data streams in the cluster, along with their backing
+    /// indices, sorted by name. The backing indices of a stream show up in
+    /// `get_indices` under the stream's name.
+    ///
+    /// # Returns
+    ///
+    /// * `Vector` of `RawDataStream`s, if successful
+    /// * `EcError` instance
+    pub fn streams(&self) -> Result<Vec<RawDataStream>, EcError> {
+        let mut streams = self.ec.get_data_streams()?;
+        streams.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(streams)
+    }
+
     /// Retreve the dated index names matching the query parameters
     ///
     /// # Arguments
     ///
-    /// `name`  - Optional base name string (sans date) of the index
-    /// `start` - Optional starting offset, in days, from today (where start > end)
-    /// `end`   - Optional ending offset, in days, from today (where end < start)
+    /// `name`  - Optional filter on the base name (sans date) of the index
+    /// `range` - The dates the indices must lie within
     ///
     /// # Returns
     ///
     /// * `Vector` of `String`s of the form <name>-<date>, if successful
     /// * `ExError` instance
-    pub fn query(&self, name: Option<String>, start: Option<i32>, end: Option<i32>)
+    pub fn query(&self, name: Option<NameFilter>, range: &DateRange)
     -> Result<Vec<String>, EcError> {
 
-        let mut results: Vec<Index> = self.get_indices(name, start, end)?;
+        let mut results: Vec<Index> = self.get_indices(name, range)?;
         results.sort_unstable();
         let return_results =
             results.iter()
-            .map(|r| format!("{}-{}", r.name, r.date))
+            .map(|r| format!("{}", r))
             .collect::<Vec<String>>();
 
         return Ok(return_results);
@@ -143,7 +284,7 @@ impl<'a, 'b> CmdProcessor<'a, 'b> {
     {
         // retrive a vector of indices
         let mut indices: Vec<Index> =
-            self.get_indices(Some(I::NAME.to_string()), start, end)?;
+            self.get_indices(Some(I::NAME.into()), &DateRange::from_offsets(start, end))?;
         //sort them
         indices.sort_unstable();
         // retrieve the results
@@ -155,47 +296,1374 @@ impl<'a, 'b> CmdProcessor<'a, 'b> {
     ///
     /// # Parameters
     ///
-    /// * `name`    - Base name of index (sans date) we are interested in
-    /// * `start`   - Optional starting offset in days to begin search
-    /// * `end`     - Optional ending offset in days to end search
+    /// * `name`    - Filter on the base names of the indices (sans date)
+    /// * `range`   - The dates the indices must lie within
+    /// * `snapshot_repo` - Optional snapshot repository to snapshot the
+    ///   indices into before deleting them
     /// * `dry_run` - Boolean indicating whether to actually perform
     ///               the delete operation or only report on what would
     ///               get deleted if run outside of dry_run mode
     ///
     /// # Returns
     ///
+    /// * `Deletion` describing what was, or would be, deleted, if successful
+    /// * `EcError`instance if unsuccessful
+    pub fn delete(&self, name: NameFilter, range: &DateRange, snapshot_repo: Option<&str>, dry_run: bool)
+    -> Result<Deletion, EcError> {
+
+        // make sure that we keep the minimum number of indices of each basename
+        // no matter what the user requests
+        let results: Vec<(Index, RawIndex)> = self.get_raw_indices(Some(name), range)?
+            .into_iter()
+            .filter(|(idx, _)| {
+                let min_days = self.config.min_days_for(idx.name()) as i64;
+                if self.age(idx) <= min_days {
+                    debug!("delete - {} falls within the minimum days for {}. Keeping it", idx, idx.name());
+                    return false;
+                }
+                true
+            })
+            .collect();
+
+        self.delete_or_report(results, snapshot_repo, dry_run)
+    }
+
+    /// Delete the oldest indices of the matching basenames until their total
+    /// store size fits within a budget. Indices within their basename's
+    /// minimum number of days are never deleted, even if that means the
+    /// budget cannot be met.
+    ///
+    /// # Parameters
+    ///
+    /// * `name`      - Filter on the base names of the indices (sans date)
+    /// * `max_bytes` - The size budget for the matching indices, in bytes
+    /// * `snapshot_repo` - Optional snapshot repository to snapshot the
+    ///   indices into before deleting them
+    /// * `dry_run`   - Report what would be deleted without deleting anything
+    ///
+    /// # Returns
+    ///
+    /// * `Deletion` describing what was, or would be, deleted, if successful
+    /// * `EcError`instance if unsuccessful
+    pub fn delete_to_size(&self, name: NameFilter, max_bytes: u64, snapshot_repo: Option<&str>, dry_run: bool)
+    -> Result<Deletion, EcError> {
+
+        let indices = self.get_raw_indices(Some(name.clone()), &DateRange::all())?;
+        let results = self.select_to_size(indices, max_bytes, &self.holds()?, &self.write_indices()?)?;
+        self.delete_or_report(results, snapshot_repo, dry_run)
+    }
+
+    // Pick the oldest indices, regardless of basename, whose deletion brings
+    // the total store size within max_bytes. Indices within their minimum
+    // days are passed over, as are held indices and write indices, though the
+    // latter are kept in the selection so that they get reported. Closed
+    // indices report no size, so they are left out of the budget altogether
+    // rather than being deleted in the belief that they free nothing.
+    fn select_to_size(&self, indices: Listing, max_bytes: u64, holds: &[Hold], protected: &HashSet<String>)
+    -> Result<Listing, EcError> {
+        let mut indices = without_closed(indices);
+        // oldest first, regardless of basename
+        indices.sort_unstable_by(|a, b| a.0.last_date().cmp(&b.0.last_date()).then_with(|| a.0.cmp(&b.0)));
+
+        let mut total = 0;
+        for (_, raw) in &indices {
+            total += raw.store_bytes()?;
+        }
+        debug!("delete_to_size - indices total {} bytes. budget is {} bytes", total, max_bytes);
+
+        let mut results = Vec::new();
+        for (idx, raw) in indices {
+            if total <= max_bytes {
+                break;
+            }
+            if self.age(&idx) <= self.config.min_days_for(idx.name()) as i64 {
+                continue;
+            }
+            // held and write indices stay put, but are passed along to be reported
+            if !frees_nothing(&idx, holds, protected) {
+                total -= raw.store_bytes()?;
+            }
+            results.push((idx, raw));
+        }
+        if total > max_bytes {
+            warn!("indices are still {} bytes over budget, but the remaining ones are within their minimum days",
+                total - max_bytes);
+        }
+        Ok(results)
+    }
+
+    /// Delete all but the newest `keep` indices of each matching basename,
+    /// regardless of any gaps in their dates. As with `delete`, indices within
+    /// the basename's minimum number of days are never deleted.
+    ///
+    /// # Parameters
+    ///
+    /// * `name`    - Filter on the base names of the indices (sans date)
+    /// * `keep`    - The number of most recent indices of each basename to keep
+    /// * `snapshot_repo` - Optional snapshot repository to snapshot the
+    ///   indices into before deleting them
+    /// * `dry_run` - Report what would be deleted without deleting anything
+    ///
+    /// # Returns
+    ///
+    /// * `Deletion` describing what was, or would be, deleted, if successful
+    /// * `EcError`instance if unsuccessful
+    pub fn delete_keep_last(&self, name: NameFilter, keep: usize, snapshot_repo: Option<&str>, dry_run: bool)
+    -> Result<Deletion, EcError> {
+
+        let indices = self.get_raw_indices(Some(name), &DateRange::all())?;
+        let results = self.select_keep_last(indices, keep);
+        self.delete_or_report(results, snapshot_repo, dry_run)
+    }
+
+    // Pick all but the newest keep indices of each basename, passing over
+    // those within the basename's minimum days
+    fn select_keep_last(&self, indices: Listing, keep: usize) -> Listing {
+        let mut by_name: BTreeMap<String, Listing> = BTreeMap::new();
+        for (idx, raw) in indices {
+            by_name.entry(idx.name.clone()).or_default().push((idx, raw));
+        }
+
+        let mut results = Vec::new();
+        for (name, mut indices) in by_name {
+            let min_days = self.config.min_days_for(&name) as i64;
+            // newest first
+            indices.sort_unstable_by(|a, b| b.0.cmp(&a.0));
+            results.extend(indices.into_iter()
+                .skip(keep)
+                .filter(|(idx, _)| self.age(idx) > min_days));
+        }
+        results
+    }
+
+    /// Delete indices when any node's disk usage exceeds the `high` percentage.
+    /// Indices of all the basenames with a policy in the config are candidates,
+    /// and are deleted oldest first, by the last day they hold and regardless
+    /// of basename, until the usage of every node is projected to fall below
+    /// the `low` percentage. Closed indices, whose size is unknown, are not
+    /// candidates. Indices within their basename's minimum number of days are
+    /// never deleted, and the indices of a policy with a `snapshot_repo` are
+    /// snapshotted first.
+    ///
+    /// The space freed on each node is projected in proportion to that node's
+    /// share of the cluster's index data.
+    ///
+    /// # Parameters
+    ///
+    /// * `high`    - Node disk usage percentage which triggers cleanup
+    /// * `low`     - Node disk usage percentage to clean up to
+    /// * `dry_run` - Report what would be deleted without deleting anything
+    ///
+    /// # Returns
+    ///
     /// * `()` if successful
     /// * `EcError`instance if unsuccessful
-    pub fn delete(&self, name: String, start: Option<i32>, end: i32, dry_run: bool)
+    pub fn delete_to_watermark(&self, high: u32, low: u32, dry_run: bool)
     -> Result<(), EcError> {
 
-        // make sure that we keep the minimum number of indices no matter what the user
-        // requests
-        let end_new = if end > self.config.min_days as i32 { end } else {
-            debug!("process_delete requested end value {} falls within MIN_DAYS.
-            Using MIN_DAYS {} ", end, self.config.min_days);
+        if low > high {
+            return Err(EcError::InvalidArgument(format!("low watermark {} is above high watermark {}", low, high)));
+        }
+
+        let mut nodes = Vec::new();
+        for alloc in self.ec.get_allocation()?.into_iter().filter(|a| a.is_node()) {
+            nodes.push(NodeUsage {
+                used: alloc.used_bytes()?,
+                total: alloc.total_bytes()?,
+                indices: alloc.indices_bytes()?,
+                name: alloc.node,
+            });
+        }
+        let cluster_indices = nodes.iter().map(|n| n.indices).sum::<u64>();
+
+        for node in &nodes {
+            info!("node {} disk usage {:.1}%", node.name, node.projected_percent(0, cluster_indices));
+        }
+        let usage = max_percent(&nodes, 0, cluster_indices);
+        if usage <= high as f64 {
+            info!("disk usage {:.1}% is below the high watermark {}%. nothing to do", usage, high);
+            return Ok(());
+        }
+
+        // gather up the deletable indices of every configured basename, oldest first
+        let mut candidates = Vec::new();
+        for (idx, raw) in without_closed(self.get_raw_indices(None, &DateRange::all())?) {
+            if !self.config.policies.contains_key(idx.name()) {
+                continue;
+            }
+            if self.age(&idx) > self.config.min_days_for(idx.name()) as i64 {
+                candidates.push((idx, raw));
+            }
+        }
+        candidates.sort_unstable_by(|a, b| a.0.last_date().cmp(&b.0.last_date()).then_with(|| a.0.cmp(&b.0)));
+
+        let holds = self.holds()?;
+        let protected = self.write_indices()?;
+        let mut freed = 0;
+        let mut results = Vec::new();
+        for (idx, raw) in candidates {
+            if max_percent(&nodes, freed, cluster_indices) < low as f64 {
+                break;
+            }
+            // held and write indices stay put, but are passed along to be reported
+            if !frees_nothing(&idx, &holds, &protected) {
+                freed += raw.store_bytes()?;
+            }
+            results.push((idx, raw));
+        }
+
+        let projected = max_percent(&nodes, freed, cluster_indices);
+        if projected >= low as f64 {
+            warn!("disk usage is projected to remain at {:.1}%, above the low watermark {}%", projected, low);
+        }
+        if dry_run {
+            println!("disk usage {:.1}% is projected to fall to {:.1}%", usage, projected);
+        }
+
+        // each basename is snapshotted into the repository its policy names,
+        // if any, before it is deleted
+        let mut by_name: BTreeMap<String, Listing> = BTreeMap::new();
+        for (idx, raw) in results {
+            by_name.entry(idx.name.clone()).or_default().push((idx, raw));
+        }
+        for (name, results) in by_name {
+            let repo = self.config.policies.get(&name)
+                .and_then(|p| p.delete.as_ref())
+                .and_then(|d| d.snapshot_repo.as_deref());
+            self.delete_or_report(results, repo, dry_run)?.print();
+        }
+        Ok(())
+    }
+
+    /// Snapshot indices into a snapshot repository, waiting for the snapshot
+    /// to complete and verifying that every index was captured in full.
+    ///
+    /// # Parameters
+    ///
+    /// * `repo`    - The name of the snapshot repository
+    /// * `indices` - The indices to snapshot
+    ///
+    /// # Returns
+    ///
+    /// * The name of the snapshot if successful
+    /// * `EcError::SnapshotError` if the snapshot did not succeed for every index
+    pub fn snapshot(&self, repo: &str, indices: &[Index]) -> Result<String, EcError> {
+        let prefix = indices.first().map(|i| i.name()).unwrap_or("indices");
+        let snapshot = format!("elasticlean-{}-{}", prefix, self.clock.now().format("%Y.%m.%d-%H.%M.%S"));
+        info!("snapshotting {} indices to {}/{}", indices.len(), repo, snapshot);
+
+        self.ec.create_snapshot(repo, &snapshot, indices)?;
+
+        let mut waited = 0;
+        let status = loop {
+            let status = self.ec.get_snapshot_status(repo, &snapshot)?;
+            if !status.in_progress() {
+                break status;
+            }
+            if waited >= SNAPSHOT_TIMEOUT_SECS {
+                return Err(EcError::SnapshotError(
+                    format!("{}/{} did not complete within {} seconds", repo, snapshot, waited)));
+            }
+            debug!("snapshot {}/{} is {}", repo, snapshot, status.state);
+            thread::sleep(Duration::from_secs(SNAPSHOT_POLL_SECS));
+            waited += SNAPSHOT_POLL_SECS;
+        };
+
+        if !status.succeeded() {
+            return Err(EcError::SnapshotError(
+                format!("{}/{} finished in state {}", repo, snapshot, status.state)));
+        }
+
+        let failed = indices.iter()
+            .map(|i| format!("{}", i))
+            .filter(|i| !status.index_succeeded(i))
+            .collect::<Vec<String>>();
+        if !failed.is_empty() {
+            return Err(EcError::SnapshotError(
+                format!("{}/{} is missing indices {}", repo, snapshot, failed.join(","))));
+        }
+
+        Ok(snapshot)
+    }
+
+    // Run the non destructive lifecycle stages of a policy. Each stage applies
+    // to the open indices older than its `after` value, up to the age at which
+    // a more severe stage takes over; there is no point in merging an index
+    // which is about to be closed, or closing one which is about to be deleted.
+    // Indices which are already merged, or already read only, are left alone.
+    fn apply_stages(&self, name: &str, policy: &Policy, dry_run: bool) -> Result<(), EcError> {
+        if policy.forcemerge.is_none() && policy.read_only.is_none() && policy.close.is_none() {
+            return Ok(());
+        }
+
+        let indices = self.get_raw_indices(Some(name.into()), &DateRange::all())?;
+        let protected = self.write_indices()?;
+        let (merge_until, delete_after) = self.stage_limits(name, policy);
+        let select = |after: u32, until: Option<i64>| self.select_stage(&indices, &protected, after, until);
+
+        if let Some(ref stage) = policy.forcemerge {
+            let mut results = select(stage.after, merge_until);
+            if !results.is_empty() {
+                let merged = self.ec.get_merged(&results, stage.max_num_segments)?;
+                results.retain(|idx| !merged.contains(&idx.to_string()));
+            }
+            if !report_stage("forcemerge", name, &results, dry_run) {
+                let results = self.ec.forcemerge_indices(&results, stage.max_num_segments)?;
+                info!("forcemerge results: {:#?}", results);
+            }
+        }
+
+        if let Some(ref stage) = policy.read_only {
+            let mut results = select(stage.after, merge_until);
+            if !results.is_empty() {
+                let blocked = self.ec.get_write_blocked(&results)?;
+                results.retain(|idx| !blocked.contains(&idx.to_string()));
+            }
+            if !report_stage("read_only", name, &results, dry_run) {
+                let results = self.ec.update_settings(&results, &json!({"index.blocks.write": true}))?;
+                info!("read_only results: {:#?}", results);
+            }
+        }
+
+        if let Some(ref stage) = policy.close {
+            let results = select(stage.after, delete_after);
+            if !report_stage("close", name, &results, dry_run) {
+                let results = self.ec.close_indices(&results)?;
+                info!("close results: {:#?}", results);
+            }
+        }
+
+        Ok(())
+    }
 
-            self.config.min_days as i32
+    // The ages beyond which a more severe stage takes over from a policy's
+    // force merge and read only stages, and from its close stage: the age
+    // at which the index is closed or deleted, whichever comes first, and
+    // the age at which it is deleted.
+    fn stage_limits(&self, name: &str, policy: &Policy) -> (Option<i64>, Option<i64>) {
+        let delete_after = policy.delete.as_ref()
+            .map(|d| cmp::max(d.retain, self.config.min_days_for(name)) as i64);
+        let close_after = policy.close.as_ref().map(|c| c.after as i64);
+        let merge_until = match (close_after, delete_after) {
+            (Some(c), Some(d)) => Some(cmp::min(c, d)),
+            (c, d) => c.or(d),
         };
+        (merge_until, delete_after)
+    }
+
+    // Select the open indices, other than write indices, older than after
+    // days and no older than until days
+    fn select_stage(&self, indices: &[(Index, RawIndex)], protected: &HashSet<String>, after: u32, until: Option<i64>)
+    -> Vec<Index> {
+        indices.iter()
+            .filter(|(idx, raw)| raw.is_open() && self.age(idx) > after as i64)
+            .filter(|(idx, _)| !protected.contains(&idx.to_string()))
+            .filter(|(idx, _)| until.map(|u| self.age(idx) <= u).unwrap_or(true))
+            .map(|(idx, _)| idx.clone())
+            .collect()
+    }
 
-        let results: Vec<Index> =
-            self.get_indices(Some(name.clone()), start, Some(end_new))?;
+    /// Restore dated indices from the snapshots in a repository. The name and
+    /// range criteria select indices exactly as they do for `get_indices`,
+    /// but are applied to the contents of the repository's successful snapshots.
+    /// Each index is restored from the most recent snapshot which holds it.
+    /// Indices which already exist in the cluster (under their restored
+    /// name) are skipped.
+    ///
+    /// # Parameters
+    ///
+    /// * `name`          - Base name of index (sans date) we are interested in
+    /// * `range`         - The dates the indices must lie within
+    /// * `repo`          - The name of the snapshot repository
+    /// * `rename_prefix` - Optional prefix to restore the indices under
+    /// * `dry_run`       - Report what would be restored without restoring anything
+    ///
+    /// # Returns
+    ///
+    /// * `()` if successful
+    /// * `EcError`instance if unsuccessful
+    pub fn restore(&self, name: String, range: &DateRange, repo: &str,
+                   rename_prefix: Option<&str>, dry_run: bool)
+    -> Result<(), EcError> {
+
+        let name = Some(NameFilter::from(name));
+        let snapshots = self.ec.get_snapshots(repo)?;
+        let existing = self.ec.get_raw_indices()?
+            .into_iter()
+            .map(|r| r.index)
+            .collect::<HashSet<String>>();
+        let by_snapshot = restore_sources(snapshots, &existing, rename_prefix, |index| {
+            self.matcher.parse(index)
+                .map(|idx| matches(&idx, &name, range, self.today()))
+                .unwrap_or(false)
+        });
+
+        let mut sz = 0;
+        for (snapshot, indices) in &by_snapshot {
+            sz += indices.len();
+            if dry_run {
+                println!("restore from {}/{}: {}", repo, snapshot, indices.join(","));
+            } else {
+                let results = self.ec.restore_snapshot(repo, snapshot, indices, rename_prefix)?;
+                info!("restore results: {:#?}", results);
+            }
+        }
+
+        if dry_run {
+            println!("{} indices will be restored", sz);
+            println!("dry-run");
+        }
+
+        Ok(())
+    }
+
+    /// Roll the daily indices of a basename for a month up into a single
+    /// monthly index (eg `foo-2018.10.01` .. `foo-2018.10.31` into `foo-2018.10`).
+    /// Dailies named after a naming pattern are rolled up into an index
+    /// named after the pattern's monthly companion (eg `foo_201810`).
+    /// The dailies are reindexed into the monthly index, and only deleted once
+    /// the monthly index holds as many documents as they do. The month must
+    /// be over, and the monthly index must not already exist. None of the
+    /// dailies may be closed, held, or a write index, since they would be
+    /// left next to the monthly index, their documents counted twice.
+    ///
+    /// # Parameters
+    ///
+    /// * `name`    - Base name of index (sans date) we are interested in
+    /// * `month`   - The month to compact, as `YYYY.MM`
+    /// * `dry_run` - Report what would be compacted without changing anything
+    ///
+    /// # Returns
+    ///
+    /// * `()` if successful
+    /// * `EcError`instance if unsuccessful
+    pub fn compact(&self, name: String, month: &str, dry_run: bool) -> Result<(), EcError> {
+        let period = Index::from_str(&format!("{}-{}", name, month))?;
+        if period.granularity != Granularity::Monthly {
+            return Err(EcError::InvalidArgument(format!("month {} is not of the form YYYY.MM", month)));
+        }
+        if self.age(&period) <= 0 {
+            return Err(EcError::InvalidArgument(format!("month {} is not over yet", month)));
+        }
+
+        let indices = self.get_raw_indices(Some(name.as_str().into()), &DateRange::all())?;
+        let existing = indices.iter().map(|(idx, _)| idx.to_string()).collect::<HashSet<String>>();
+        let mut dailies = indices.into_iter()
+            .filter(|(idx, _)| idx.granularity == Granularity::Daily)
+            .filter(|(idx, _)| idx.date().year() == period.date().year() && idx.date().month() == period.date().month())
+            .collect::<Vec<(Index, RawIndex)>>();
+        dailies.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+        if dailies.is_empty() {
+            info!("no daily {} indices in {}", name, month);
+            return Ok(());
+        }
+
+        let target = self.matcher.monthly(&dailies[0].0);
+        if existing.contains(&target.to_string()) {
+            return Err(EcError::CompactError(format!("{} already exists", target)));
+        }
+        if let Some((idx, _)) = dailies.iter().find(|(_, raw)| !raw.is_open()) {
+            return Err(EcError::CompactError(format!("{} is closed", idx)));
+        }
+        let holds = self.holds()?;
+        if let Some((idx, hold)) = dailies.iter().find_map(|(idx, _)| holds.iter().find(|h| h.holds(idx)).map(|h| (idx, h))) {
+            return Err(EcError::CompactError(format!("{} is held by {}", idx, hold)));
+        }
+        let protected = self.write_indices()?;
+        if let Some((idx, _)) = dailies.iter().find(|(idx, _)| protected.contains(&idx.to_string())) {
+            return Err(EcError::CompactError(format!("{} is a write index", idx)));
+        }
+
+        let mut expected = 0;
+        for (_, raw) in &dailies {
+            expected += raw.doc_count()?;
+        }
+        let indices = dailies.iter().map(|(idx, _)| idx.clone()).collect::<Vec<Index>>();
 
         if dry_run {
-            let sz = results.len();
-            let idxs = results.into_iter()
-                .map(|i| format!("{}",i))
-                .collect::<Vec<String>>()
-                .join(",");
-
-            println!("joined indices for delete: {} ",idxs);
-            println!("{} indices will be deleted", sz);
+            println!("{} indices holding {} documents will be compacted into {}", indices.len(), expected, target);
             println!("dry-run");
+            return Ok(());
+        }
+
+        let task = self.ec.reindex(&indices, &target)?;
+        info!("reindexing {} indices into {}. task {}", indices.len(), target, task);
+
+        let mut waited = 0;
+        let status = loop {
+            let status = self.ec.get_task(&task)?;
+            if status.completed {
+                break status;
+            }
+            if waited >= REINDEX_TIMEOUT_SECS {
+                return Err(EcError::CompactError(
+                    format!("reindex into {} did not complete within {} seconds", target, waited)));
+            }
+            thread::sleep(Duration::from_secs(REINDEX_POLL_SECS));
+            waited += REINDEX_POLL_SECS;
+        };
+        if let Some(failure) = status.failure() {
+            return Err(EcError::CompactError(format!("reindex into {} failed: {}", target, failure)));
+        }
+
+        self.ec.refresh_indices(std::slice::from_ref(&target))?;
+        let actual = match self.ec.get_raw_indices()?.into_iter().find(|r| r.index == format!("{}", target)) {
+            Some(raw) => raw.doc_count()?,
+            None => 0,
+        };
+        if actual != expected {
+            return Err(EcError::CompactError(
+                format!("{} holds {} documents but the daily indices hold {}. the daily indices were kept",
+                    target, actual, expected)));
+        }
+
+        self.delete_or_report(dailies, None, false)?;
+        Ok(())
+    }
+
+    // Delete the supplied indices, or, in dry_run mode, work out what would
+    // get deleted. Held indices and write indices are never deleted. If a
+    // snapshot repository is supplied, nothing is deleted unless the indices
+    // are first snapshotted successfully. Elasticsearch deletes the indices
+    // all together or not at all, so a failed delete is an error rather than
+    // a Deletion.
+    fn delete_or_report(&self, results: Vec<(Index, RawIndex)>, snapshot_repo: Option<&str>, dry_run: bool)
+    -> Result<Deletion, EcError> {
+        let (results, held) = self.without_held(results)?;
+        let (results, write_indices) = self.without_write_indices(results)?;
+        let deletion = Deletion {
+            deleted: results,
+            held,
+            write_indices,
+            snapshot_repo: snapshot_repo.map(String::from),
+            dry_run,
+        };
+        if dry_run {
+            return Ok(deletion);
+        }
+
+        if deletion.deleted.is_empty() {
+            info!("no indices to delete");
         } else {
-            let results = self.ec.delete_indices(&results);
+            let results = deletion.deleted.iter().map(|(idx, _)| idx.clone()).collect::<Vec<Index>>();
+            if let Some(repo) = snapshot_repo {
+                let snapshot = self.snapshot(repo, &results)?;
+                info!("snapshot {}/{} succeeded", repo, snapshot);
+            }
+            let results = self.ec.delete_indices(&results)?;
             info!("delete results: {:#?}", results);
         }
 
+        Ok(deletion)
+    }
+
+    /// Retrieve the holds which protect indices from deletion: those in the
+    /// config followed by those in the state file.
+    ///
+    /// # Returns
+    ///
+    /// * `Vector` of `Hold`s, if successful
+    /// * `EcError` if the state file cannot be read
+    pub fn holds(&self) -> Result<Vec<Hold>, EcError> {
+        let store = HoldStore::load(&self.config.state_file)?;
+        Ok(self.config.holds.iter().chain(store.holds()).cloned().collect())
+    }
+
+    /// Record a hold in the state file. A hold on the same indices replaces
+    /// any existing one.
+    ///
+    /// # Parameters
+    ///
+    /// * `hold` - The hold to record
+    ///
+    /// # Returns
+    ///
+    /// * `()` if successful
+    /// * `EcError` if the hold is invalid or the state file cannot be written
+    pub fn protect(&self, hold: Hold) -> Result<(), EcError> {
+        let mut store = HoldStore::load(&self.config.state_file)?;
+        store.add(hold)?;
+        store.save()
+    }
+
+    /// Remove a hold from the state file. Holds in the config can only be
+    /// removed by editing the config.
+    ///
+    /// # Parameters
+    ///
+    /// * `hold` - The hold to remove; its reason is ignored
+    ///
+    /// # Returns
+    ///
+    /// * `()` if successful
+    /// * `EcError` if there is no such hold or the state file cannot be written
+    pub fn unprotect(&self, hold: &Hold) -> Result<(), EcError> {
+        let mut store = HoldStore::load(&self.config.state_file)?;
+        if !store.remove(hold) {
+            return Err(EcError::InvalidArgument(format!("no hold on {} in {}", hold, self.config.state_file.display())));
+        }
+        store.save()
+    }
+
+    // Split the held indices, along with their holds, from a set of indices
+    // about to be deleted.
+    fn without_held(&self, results: Vec<(Index, RawIndex)>)
+    -> Result<(Listing, Vec<(Index, Hold)>), EcError> {
+        if results.is_empty() {
+            return Ok((results, Vec::new()));
+        }
+        let holds = self.holds()?;
+
+        let mut kept = Vec::new();
+        let mut held = Vec::new();
+        for (idx, raw) in results {
+            match holds.iter().find(|h| h.holds(&idx)) {
+                Some(hold) => {
+                    info!("skipping {}, held by {}", idx, hold);
+                    held.push((idx, hold.clone()));
+                }
+                None => kept.push((idx, raw)),
+            }
+        }
+        Ok((kept, held))
+    }
+
+    // The aliases defined in the cluster, looked up once per command
+    fn aliases(&self) -> Result<Vec<RawAlias>, EcError> {
+        if let Some(ref aliases) = *self.aliases.borrow() {
+            return Ok(aliases.clone());
+        }
+        let aliases = self.ec.get_aliases()?;
+        *self.aliases.borrow_mut() = Some(aliases.clone());
+        Ok(aliases)
+    }
+
+    // The names of the indices which must never be deleted or closed: the write
+    // index of every alias and data stream, and the newest generation of every
+    // rollover series, which is the one rollover writes to even if no alias
+    // says so. They are looked up once, rather than for every delete and
+    // lifecycle stage a command runs.
+    fn write_indices(&self) -> Result<HashSet<String>, EcError> {
+        if let Some(ref protected) = *self.protected.borrow() {
+            return Ok(protected.clone());
+        }
+
+        let mut protected = write_indices(&self.aliases()?);
+
+        let mut newest: BTreeMap<String, (u32, String)> = BTreeMap::new();
+        let mut has_streams = false;
+        for (idx, raw) in self.get_raw_indices(None, &DateRange::all())? {
+            if let Granularity::Rollover(generation) = idx.granularity {
+                has_streams |= raw.index.starts_with(".ds-");
+                let entry = newest.entry(idx.name.clone()).or_insert((generation, idx.to_string()));
+                if generation > entry.0 {
+                    *entry = (generation, idx.to_string());
+                }
+            }
+        }
+        protected.extend(newest.into_iter().map(|(_, (_, name))| name));
+
+        // only clusters holding backing indices know about data streams
+        if has_streams {
+            let streams = self.ec.get_data_streams()?;
+            protected.extend(streams.iter().filter_map(|s| s.write_index()).map(String::from));
+        }
+
+        *self.protected.borrow_mut() = Some(protected.clone());
+        Ok(protected)
+    }
+
+    // Split the write indices from a set of indices about to be deleted.
+    fn without_write_indices(&self, results: Vec<(Index, RawIndex)>)
+    -> Result<(Listing, Vec<Index>), EcError> {
+        if results.is_empty() {
+            return Ok((results, Vec::new()));
+        }
+        let protected = self.write_indices()?;
+
+        let mut kept = Vec::new();
+        let mut writing = Vec::new();
+        for (idx, raw) in results {
+            if protected.contains(&idx.to_string()) {
+                info!("skipping write index {}", idx);
+                writing.push(idx);
+            } else {
+                kept.push((idx, raw));
+            }
+        }
+        Ok((kept, writing))
+    }
+
+    /// Apply the retention policies from the config. The force merge, read only,
+    /// and close stages of each policy are run first, followed by the delete stage,
+    /// which deletes the indices of the basename that are older than the policy's
+    /// `retain` value. Stages which are absent from a policy are skipped.
+    ///
+    /// # Parameters
+    ///
+    /// * `dry_run` - Report what would be deleted without deleting anything
+    ///
+    /// # Returns
+    ///
+    /// * `()` if successful
+    /// * `EcError`instance if unsuccessful
+    pub fn apply(&self, dry_run: bool) -> Result<(), EcError> {
+        for (name, policy) in &self.config.policies {
+            self.apply_stages(name, policy, dry_run)?;
+            if let Some(ref delete) = policy.delete {
+                debug!("apply - deleting {} indices older than {} days", name, delete.retain);
+                self.delete(name.as_str().into(), &DateRange::from_offsets(None, Some(delete.retain as i32)), delete.snapshot_repo.as_deref(), dry_run)?.print();
+            }
+        }
+
         Ok(())
     }
 }
+
+// Print out the indices a lifecycle stage touches in dry_run mode. Returns
+// true if there is nothing more to do, either because this is a dry run or
+// because there are no indices to act upon.
+fn report_stage(stage: &str, name: &str, results: &[Index], dry_run: bool) -> bool {
+    if dry_run {
+        let idxs = results.iter()
+            .map(|i| format!("{}",i))
+            .collect::<Vec<String>>()
+            .join(",");
+        println!("{} {}: {}", stage, name, idxs);
+        println!("{} indices will be affected by {}", results.len(), stage);
+        return true;
+    }
+    if results.is_empty() {
+        info!("no {} indices to {}", name, stage);
+        return true;
+    }
+    false
+}
+
+// Indices paired with the metadata elasticsearch returned for them
+type Listing = Vec<(Index, RawIndex)>;
+
+/// The outcome of a delete: the indices which were deleted, or in dry_run
+/// mode would be, along with the indices which were held back because they
+/// are held or are write indices.
+#[derive(Debug)]
+pub struct Deletion {
+    pub deleted: Vec<(Index, RawIndex)>,
+    pub held: Vec<(Index, Hold)>,
+    pub write_indices: Vec<Index>,
+    pub snapshot_repo: Option<String>,
+    pub dry_run: bool,
+}
+
+impl Deletion {
+    /// In dry_run mode, print out what would get deleted, grouped by
+    /// basename, and what would be held back. Outside of dry_run mode
+    /// the delete has already been logged, and nothing is printed.
+    pub fn print(&self) {
+        if !self.dry_run {
+            return;
+        }
+        for (idx, hold) in &self.held {
+            println!("{} is held by {} and will not be deleted", idx, hold);
+        }
+        for idx in &self.write_indices {
+            println!("{} is a write index and will not be deleted", idx);
+        }
+
+        let sz = self.deleted.len();
+        // group the indices by basename, so that each series affected stands out
+        let mut by_name: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+        for (idx, _) in &self.deleted {
+            by_name.entry(idx.name()).or_default().push(format!("{}", idx));
+        }
+
+        for (name, idxs) in by_name {
+            println!("{} ({} indices) for delete: {} ", name, idxs.len(), idxs.join(","));
+        }
+        if let Some(ref repo) = self.snapshot_repo {
+            println!("{} indices will be snapshotted to {}", sz, repo);
+        }
+        println!("{} indices will be deleted", sz);
+        println!("dry-run");
+    }
+}
+
+// Leave the closed indices out of a listing. Closed indices report no size,
+// so they cannot be weighed against a size budget or disk usage.
+fn without_closed(indices: Listing) -> Listing {
+    indices.into_iter()
+        .filter(|(idx, raw)| {
+            if !raw.is_open() {
+                warn!("{} is closed and reports no size. leaving it out", idx);
+            }
+            raw.is_open()
+        })
+        .collect()
+}
+
+// Whether deleting an index frees nothing, because delete_or_report passes it
+// over: it is held, or it is a write index
+fn frees_nothing(idx: &Index, holds: &[Hold], protected: &HashSet<String>) -> bool {
+    holds.iter().any(|h| h.holds(idx)) || protected.contains(&idx.to_string())
+}
+
+// Work out which snapshot to restore each index from: the newest successful
+// snapshot holding it. Indices which the filter rejects, or which already
+// exist under their restored name, are left out. The indices to restore are
+// returned keyed by snapshot.
+fn restore_sources<F>(mut snapshots: Vec<RawSnapshot>, existing: &HashSet<String>, rename_prefix: Option<&str>,
+                      wanted: F) -> BTreeMap<String, Vec<String>>
+where
+    F: Fn(&str) -> bool
+{
+    snapshots.sort_by_key(|s| s.start_time_in_millis);
+
+    // map each matching index to the newest snapshot containing it
+    let mut sources: BTreeMap<String, String> = BTreeMap::new();
+    for snap in snapshots.iter().filter(|s| s.state == "SUCCESS") {
+        for index in snap.indices.iter().filter(|i| wanted(i)) {
+            sources.insert(index.clone(), snap.snapshot.clone());
+        }
+    }
+
+    let mut by_snapshot: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (index, snapshot) in sources {
+        let target = format!("{}{}", rename_prefix.unwrap_or(""), index);
+        if existing.contains(&target) {
+            warn!("{} already exists. skipping restore of {}", target, index);
+            continue;
+        }
+        by_snapshot.entry(snapshot).or_default().push(index);
+    }
+    by_snapshot
+}
+
+// Whether an index matches the optional name and date range criteria
+// shared by the query, delete, and restore commands.
+fn matches(v: &Index, name: &Option<NameFilter>, range: &DateRange, today: NaiveDate) -> bool {
+    // if name is supplied match against it
+    if let Some(ref n) = name {
+        if !n.matches(&v.name) {
+            return false;
+        }
+    }
+    range.matches(v, today)
+}
+
+// Disk usage of a single node, in bytes
+struct NodeUsage {
+    name: String,
+    used: u64,
+    total: u64,
+    indices: u64,
+}
+
+impl NodeUsage {
+    // The node's disk usage percentage once `freed` bytes of index data have
+    // been deleted from the cluster, assuming the node gives up its share of them.
+    fn projected_percent(&self, freed: u64, cluster_indices: u64) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        let share = if cluster_indices == 0 { 0.0 } else {
+            self.indices as f64 / cluster_indices as f64
+        };
+        let used = (self.used as f64 - freed as f64 * share).max(0.0);
+        used / self.total as f64 * 100.0
+    }
+}
+
+// the projected disk usage percentage of the fullest node
+fn max_percent(nodes: &[NodeUsage], freed: u64, cluster_indices: u64) -> f64 {
+    nodes.iter()
+        .map(|n| n.projected_percent(freed, cluster_indices))
+        .fold(0.0, f64::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FixedClock;
+    use crate::config::{DeletePolicy, ForceMergeStage, Stage};
+    use chrono::{DateTime, Utc};
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+
+    // A cluster which answers each request with the status and body of the
+    // first reply whose prefix the request line (eg `GET /_cat/indices?..`)
+    // starts with, or a 404 if none does. The request lines are recorded.
+    fn cluster(replies: Vec<(&'static str, &'static str, &'static str)>) -> (u16, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let request = read_request(&mut stream);
+                let line = request.lines().next().unwrap_or("").to_string();
+                let (status, body) = replies.iter()
+                    .find(|(prefix, _, _)| line.starts_with(prefix))
+                    .map(|(_, status, body)| (*status, *body))
+                    .unwrap_or(("404 Not Found", "{}"));
+                seen.lock().unwrap().push(line);
+                let reply = format!("HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                                    status, body.len(), body);
+                let _ = stream.write_all(reply.as_bytes());
+            }
+        });
+        (port, requests)
+    }
+
+    // Read a request's head and its body, if it has one
+    fn read_request(stream: &mut impl Read) -> String {
+        let mut request = Vec::new();
+        let mut buf = [0; 4096];
+        loop {
+            let n = stream.read(&mut buf).unwrap_or(0);
+            request.extend_from_slice(&buf[..n]);
+            let text = String::from_utf8_lossy(&request).to_string();
+            let complete = match text.find("\r\n\r\n") {
+                Some(end) => {
+                    let length = text.lines()
+                        .find_map(|l| l.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().to_string()))
+                        .and_then(|v| v.parse::<usize>().ok())
+                        .unwrap_or(0);
+                    request.len() >= end + 4 + length
+                },
+                None => false,
+            };
+            if complete || n == 0 {
+                return text;
+            }
+        }
+    }
+
+    // A config pointing at a local cluster, with no holds
+    fn local(port: u16) -> Config {
+        let mut config = Config::new("127.0.0.1", port.to_string(), "3");
+        config.state_file = std::env::temp_dir().join(format!("elasticlean-test-{}.json", port));
+        config
+    }
+
+    fn raw(index: &str, size: &str) -> RawIndex {
+        RawIndex {
+            health: Some("green".to_string()),
+            status: "open".to_string(),
+            index: index.to_string(),
+            pri: Some("1".to_string()),
+            rep: Some("1".to_string()),
+            docs_count: Some("10".to_string()),
+            store_size: Some(size.to_string()),
+            pri_store_size: Some(size.to_string()),
+            creation_date: None,
+        }
+    }
+
+    fn listing(names: &[(&str, &str)]) -> Listing {
+        names.iter()
+            .map(|(name, size)| (Index::from_str(name).unwrap(), raw(name, size)))
+            .collect()
+    }
+
+    fn names(results: &[(Index, RawIndex)]) -> Vec<String> {
+        results.iter().map(|(idx, _)| idx.to_string()).collect()
+    }
+
+    fn on(config: &Config, year: i32, month: u32, day: u32) -> CmdProcessor<'_, '_> {
+        let date = NaiveDate::from_ymd_opt(year, month, day).unwrap();
+        CmdProcessor::with_clock(config, Box::new(FixedClock::on(date))).unwrap()
+    }
+
+    #[test]
+    fn select_to_size_deletes_oldest_first_outside_min_days() {
+        let config = Config::new("localhost", "9200", "3");
+        let cproc = on(&config, 2018, 10, 10);
+        let indices = || listing(&[
+            ("bar-2018.10.08", "10b"),
+            ("foo-2018.10.02", "10b"),
+            ("bar-2018.10.01", "10b"),
+            ("foo-2018.10.09", "10b"),
+            ("foo-2018.10.03", "10b"),
+        ]);
+        // 50 bytes down to 30 takes the two oldest, whatever their basename
+        let results = cproc.select_to_size(indices(), 30, &[], &HashSet::new()).unwrap();
+        assert_eq!(names(&results), vec!["bar-2018.10.01", "foo-2018.10.02"]);
+
+        // the indices within 3 days are never selected, even over budget
+        let results = cproc.select_to_size(indices(), 0, &[], &HashSet::new()).unwrap();
+        assert_eq!(names(&results), vec!["bar-2018.10.01", "foo-2018.10.02", "foo-2018.10.03"]);
+    }
+
+    #[test]
+    fn select_to_size_looks_past_held_indices() {
+        let config = Config::new("localhost", "9200", "3");
+        let cproc = on(&config, 2018, 10, 10);
+        let indices = listing(&[("foo-2018.10.01", "10b"), ("foo-2018.10.02", "10b"), ("foo-2018.10.03", "10b")]);
+        let hold = Hold { index: Some("foo-2018.10.01".to_string()), ..Hold::default() };
+        let results = cproc.select_to_size(indices, 20, &[hold], &HashSet::new()).unwrap();
+        assert_eq!(names(&results), vec!["foo-2018.10.01", "foo-2018.10.02"]);
+    }
+
+    #[test]
+    fn select_to_size_looks_past_write_indices() {
+        let config = Config::new("localhost", "9200", "3");
+        let cproc = on(&config, 2018, 10, 10);
+        let indices = listing(&[("foo-2018.10.01", "10b"), ("foo-2018.10.02", "10b"), ("foo-2018.10.03", "10b")]);
+        let protected = ["foo-2018.10.01".to_string()].iter().cloned().collect::<HashSet<String>>();
+        let results = cproc.select_to_size(indices, 20, &[], &protected).unwrap();
+        assert_eq!(names(&results), vec!["foo-2018.10.01", "foo-2018.10.02"]);
+    }
+
+    #[test]
+    fn watermark_looks_past_write_indices() {
+        let (port, requests) = cluster(vec![
+            ("GET /_cat/allocation", "200 OK",
+             r#"[{"shards": "2", "disk.indices": "80", "disk.used": "90", "disk.avail": "10", "disk.total": "100",
+                  "disk.percent": "90", "host": "h", "ip": "i", "node": "n"}]"#),
+            ("GET /_cat/indices", "200 OK",
+             r#"[{"health": "green", "status": "open", "index": "foo-2018.10.01", "store.size": "20b"},
+                 {"health": "green", "status": "open", "index": "foo-2018.10.02", "store.size": "20b"},
+                 {"health": "green", "status": "open", "index": "foo-2018.10.03", "store.size": "20b"}]"#),
+            ("GET /_cat/aliases", "200 OK", r#"[{"alias": "foo", "index": "foo-2018.10.01", "is_write_index": "true"}]"#),
+            ("DELETE /foo-2018.10.02", "200 OK", r#"{"acknowledged": true}"#),
+        ]);
+        let mut config = local(port);
+        config.policies.insert("foo".to_string(), Policy {
+            delete: Some(DeletePolicy { retain: 5, snapshot_repo: None }),
+            ..Policy::default()
+        });
+        let cproc = on(&config, 2018, 10, 20);
+
+        // 90% down to below 75% takes 20 bytes beyond the write index
+        cproc.delete_to_watermark(85, 75, false).unwrap();
+        let requests = requests.lock().unwrap();
+        let deletes = requests.iter().filter(|r| r.starts_with("DELETE")).collect::<Vec<&String>>();
+        assert_eq!(deletes.len(), 1);
+        assert!(deletes[0].starts_with("DELETE /foo-2018.10.02 "));
+    }
+
+    #[test]
+    fn watermark_deletes_by_the_last_day_held() {
+        let (port, requests) = cluster(vec![
+            ("GET /_cat/allocation", "200 OK",
+             r#"[{"shards": "2", "disk.indices": "80", "disk.used": "90", "disk.avail": "10", "disk.total": "100",
+                  "disk.percent": "90", "host": "h", "ip": "i", "node": "n"}]"#),
+            ("GET /_cat/indices", "200 OK",
+             r#"[{"health": "green", "status": "open", "index": "foo-2018.09", "store.size": "20b"},
+                 {"health": "green", "status": "open", "index": "foo-2018.09.15", "store.size": "20b"}]"#),
+            ("GET /_cat/aliases", "200 OK", "[]"),
+            ("DELETE /foo-2018.09.15", "200 OK", r#"{"acknowledged": true}"#),
+        ]);
+        let mut config = local(port);
+        config.policies.insert("foo".to_string(), Policy {
+            delete: Some(DeletePolicy { retain: 5, snapshot_repo: None }),
+            ..Policy::default()
+        });
+        let cproc = on(&config, 2018, 10, 20);
+
+        // the monthly index holds days up to the 30th, so the daily goes first
+        cproc.delete_to_watermark(85, 75, false).unwrap();
+        let requests = requests.lock().unwrap();
+        let deletes = requests.iter().filter(|r| r.starts_with("DELETE")).collect::<Vec<&String>>();
+        assert_eq!(deletes.len(), 1);
+        assert!(deletes[0].starts_with("DELETE /foo-2018.09.15 "));
+    }
+
+    #[test]
+    fn compact_refuses_held_and_write_indices() {
+        let (port, requests) = cluster(vec![
+            ("GET /_cat/indices", "200 OK",
+             r#"[{"health": "green", "status": "open", "index": "foo-2018.09.01", "docs.count": "1", "store.size": "20b"},
+                 {"health": "green", "status": "open", "index": "foo-2018.09.02", "docs.count": "1", "store.size": "20b"}]"#),
+            ("GET /_cat/aliases", "200 OK", r#"[{"alias": "foo", "index": "foo-2018.09.02", "is_write_index": "true"}]"#),
+        ]);
+        let mut config = local(port);
+        let cproc = on(&config, 2018, 10, 20);
+        match cproc.compact("foo".to_string(), "2018.09", true) {
+            Err(EcError::CompactError(e)) => assert!(e.contains("foo-2018.09.02 is a write index")),
+            other => panic!("expected a CompactError, got {:?}", other),
+        }
+
+        config.holds.push(Hold { index: Some("foo-2018.09.01".to_string()), ..Hold::default() });
+        let cproc = on(&config, 2018, 10, 20);
+        match cproc.compact("foo".to_string(), "2018.09", false) {
+            Err(EcError::CompactError(e)) => assert!(e.contains("foo-2018.09.01")),
+            other => panic!("expected a CompactError, got {:?}", other),
+        }
+        assert!(!requests.lock().unwrap().iter().any(|r| r.starts_with("POST")));
+    }
+
+    #[test]
+    fn select_keep_last_counts_indices_not_days() {
+        let mut config = Config::new("localhost", "9200", "3");
+        config.floors.insert("bar".to_string(), 30);
+        let cproc = on(&config, 2018, 10, 20);
+        // foo has gaps in its dates, so keeping 2 reaches back to the 1st
+        let indices = listing(&[
+            ("foo-2018.09.10", "10b"),
+            ("foo-2018.10.01", "10b"),
+            ("foo-2018.09.20", "10b"),
+            ("foo-2018.10.19", "10b"),
+            ("bar-2018.10.01", "10b"),
+            ("bar-2018.09.01", "10b"),
+            ("bar-2018.10.10", "10b"),
+        ]);
+        let mut results = names(&cproc.select_keep_last(indices, 2));
+        results.sort();
+        // bar's floor of 30 days keeps bar-2018.10.01 along with the newest 2
+        assert_eq!(results, vec!["bar-2018.09.01", "foo-2018.09.10", "foo-2018.09.20"]);
+    }
+
+    #[test]
+    fn select_keep_last_honours_min_days() {
+        let config = Config::new("localhost", "9200", "10");
+        let cproc = on(&config, 2018, 10, 20);
+        let indices = listing(&[("foo-2018.10.17", "10b"), ("foo-2018.10.18", "10b"), ("foo-2018.10.19", "10b")]);
+        assert!(cproc.select_keep_last(indices, 1).is_empty());
+    }
+
+    #[test]
+    fn watermark_snapshots_each_policy_before_deleting() {
+        let (port, requests) = cluster(vec![
+            ("GET /_cat/allocation", "200 OK",
+             r#"[{"shards": "2", "disk.indices": "80", "disk.used": "90", "disk.avail": "10", "disk.total": "100",
+                  "disk.percent": "90", "host": "h", "ip": "i", "node": "n"}]"#),
+            ("GET /_cat/indices", "200 OK",
+             r#"[{"health": "green", "status": "open", "index": "foo-2018.10.01", "store.size": "40b"},
+                 {"health": "green", "status": "open", "index": "foo-2018.10.02", "store.size": "40b"}]"#),
+            ("GET /_cat/aliases", "200 OK", "[]"),
+            ("PUT /_snapshot/backups/", "500 Internal Server Error", "{}"),
+        ]);
+        let mut config = local(port);
+        config.policies.insert("foo".to_string(), Policy {
+            delete: Some(DeletePolicy { retain: 5, snapshot_repo: Some("backups".to_string()) }),
+            ..Policy::default()
+        });
+        let cproc = on(&config, 2018, 10, 20);
+
+        assert!(cproc.delete_to_watermark(85, 75, false).is_err());
+        let requests = requests.lock().unwrap();
+        assert!(requests.iter().any(|r| r.starts_with("PUT /_snapshot/backups/elasticlean-foo-2018.10.20-00.00.00")));
+        assert!(!requests.iter().any(|r| r.starts_with("DELETE")));
+    }
+
+    fn snapshot(name: &str, state: &str, start: i64, indices: &[&str]) -> RawSnapshot {
+        RawSnapshot {
+            snapshot: name.to_string(),
+            state: state.to_string(),
+            indices: indices.iter().map(|i| i.to_string()).collect(),
+            start_time_in_millis: Some(start),
+        }
+    }
+
+    #[test]
+    fn restores_from_newest_successful_snapshot() {
+        let snapshots = vec![
+            snapshot("newest", "FAILED", 3, &["foo-2018.10.01"]),
+            snapshot("newer", "SUCCESS", 2, &["foo-2018.10.01", "foo-2018.10.02"]),
+            snapshot("old", "SUCCESS", 1, &["foo-2018.10.01", "foo-2018.09.30", "bar-2018.10.01"]),
+        ];
+        let sources = restore_sources(snapshots, &HashSet::new(), None, |i| i.starts_with("foo-"));
+        assert_eq!(sources.len(), 2);
+        assert_eq!(sources["newer"], vec!["foo-2018.10.01", "foo-2018.10.02"]);
+        assert_eq!(sources["old"], vec!["foo-2018.09.30"]);
+    }
+
+    #[test]
+    fn skips_indices_which_already_exist() {
+        let snapshots = vec![snapshot("snap", "SUCCESS", 1, &["foo-2018.10.01", "foo-2018.10.02"])];
+        let existing = ["foo-2018.10.01", "restored-foo-2018.10.02"].iter()
+            .map(|i| i.to_string())
+            .collect::<HashSet<String>>();
+        let sources = restore_sources(snapshots, &existing, None, |_| true);
+        assert_eq!(sources["snap"], vec!["foo-2018.10.02"]);
+
+        let snapshots = vec![snapshot("snap", "SUCCESS", 1, &["foo-2018.10.01", "foo-2018.10.02"])];
+        let sources = restore_sources(snapshots, &existing, Some("restored-"), |_| true);
+        assert_eq!(sources["snap"], vec!["foo-2018.10.01"]);
+    }
+
+    #[test]
+    fn apply_fails_when_a_stage_fails() {
+        let (port, requests) = cluster(vec![
+            ("GET /_cat/indices", "200 OK",
+             r#"[{"health": "green", "status": "open", "index": "foo-2018.10.01", "store.size": "40b"}]"#),
+            ("GET /_cat/aliases", "200 OK", "[]"),
+            ("POST /foo-2018.10.01/_close", "403 Forbidden", "{}"),
+        ]);
+        let mut config = local(port);
+        config.policies.insert("foo".to_string(), Policy { close: Some(Stage { after: 5 }), ..Policy::default() });
+        let cproc = on(&config, 2018, 10, 20);
+
+        assert!(cproc.apply(false).is_err());
+        assert!(requests.lock().unwrap().iter().any(|r| r.starts_with("POST /foo-2018.10.01/_close")));
+    }
+
+    #[test]
+    fn apply_skips_merged_and_read_only_indices() {
+        let (port, requests) = cluster(vec![
+            ("GET /_cat/indices", "200 OK",
+             r#"[{"health": "green", "status": "open", "index": "foo-2018.10.01", "store.size": "40b"},
+                 {"health": "green", "status": "open", "index": "foo-2018.10.02", "store.size": "40b"}]"#),
+            ("GET /_cat/aliases", "200 OK", "[]"),
+            ("GET /_cat/shards", "200 OK",
+             r#"[{"index": "foo-2018.10.01", "shard": "0", "prirep": "p", "segments.count": "1"},
+                 {"index": "foo-2018.10.02", "shard": "0", "prirep": "p", "segments.count": "12"}]"#),
+            ("GET /foo-", "200 OK",
+             r#"{"foo-2018.10.01": {"settings": {}},
+                 "foo-2018.10.02": {"settings": {"index.blocks.write": "true"}}}"#),
+            ("POST /foo-2018.10.02/_forcemerge", "200 OK", "{}"),
+            ("PUT /foo-2018.10.01/_settings", "200 OK", "{}"),
+        ]);
+        let mut config = local(port);
+        config.policies.insert("foo".to_string(), Policy {
+            forcemerge: Some(ForceMergeStage { after: 5, max_num_segments: 1 }),
+            read_only: Some(Stage { after: 5 }),
+            ..Policy::default()
+        });
+        let cproc = on(&config, 2018, 10, 20);
+
+        cproc.apply(false).unwrap();
+        let requests = requests.lock().unwrap();
+        let changes = requests.iter()
+            .filter(|r| !r.starts_with("GET"))
+            .map(|r| r.split(|c| c == ' ' || c == '?').take(2).collect::<Vec<&str>>().join(" "))
+            .collect::<Vec<String>>();
+        assert_eq!(changes, vec!["POST /foo-2018.10.02/_forcemerge", "PUT /foo-2018.10.01/_settings"]);
+    }
+
+    #[test]
+    fn select_to_size_leaves_closed_indices_alone() {
+        let config = Config::new("localhost", "9200", "3");
+        let cproc = on(&config, 2018, 10, 10);
+        let mut indices = listing(&[("foo-2018.10.01", "10b"), ("foo-2018.10.02", "10b"), ("foo-2018.10.03", "10b")]);
+        indices[0].1.status = "close".to_string();
+        indices[0].1.store_size = None;
+        let results = cproc.select_to_size(indices, 10, &[], &HashSet::new()).unwrap();
+        assert_eq!(names(&results), vec!["foo-2018.10.02"]);
+    }
+
+    #[test]
+    fn stages_apply_up_to_the_next_stage() {
+        let config = Config::new("localhost", "9200", "3");
+        let cproc = on(&config, 2018, 10, 31);
+        let policy = Policy {
+            forcemerge: Some(ForceMergeStage { after: 2, max_num_segments: 1 }),
+            close: Some(Stage { after: 10 }),
+            delete: Some(DeletePolicy { retain: 20, snapshot_repo: None }),
+            ..Policy::default()
+        };
+        let (merge_until, delete_after) = cproc.stage_limits("foo", &policy);
+        assert_eq!((merge_until, delete_after), (Some(10), Some(20)));
+
+        let mut indices = listing(&[
+            ("foo-2018.10.30", "10b"),  // 1 day old
+            ("foo-2018.10.28", "10b"),  // 3 days
+            ("foo-2018.10.21", "10b"),  // 10 days
+            ("foo-2018.10.20", "10b"),  // 11 days
+            ("foo-2018.10.11", "10b"),  // 20 days
+            ("foo-2018.10.10", "10b"),  // 21 days
+            ("foo-2018.10.25", "10b"),  // a write index
+            ("foo-2018.10.26", "10b"),  // closed
+        ]);
+        indices[7].1.status = "close".to_string();
+        let protected = ["foo-2018.10.25".to_string()].iter().cloned().collect::<HashSet<String>>();
+
+        let names = |results: Vec<Index>| results.iter().map(|i| i.to_string()).collect::<Vec<String>>();
+        assert_eq!(names(cproc.select_stage(&indices, &protected, 2, merge_until)),
+                   vec!["foo-2018.10.28", "foo-2018.10.21"]);
+        assert_eq!(names(cproc.select_stage(&indices, &protected, 10, delete_after)),
+                   vec!["foo-2018.10.20", "foo-2018.10.11"]);
+
+        // a retain within the basename's min days is stretched to them
+        let mut config = Config::new("localhost", "9200", "3");
+        config.floors.insert("foo".to_string(), 30);
+        let cproc = on(&config, 2018, 10, 31);
+        assert_eq!(cproc.stage_limits("foo", &policy), (Some(10), Some(30)));
+    }
+
+    #[test]
+    fn apply_looks_up_write_indices_once() {
+        let (port, requests) = cluster(vec![
+            ("GET /_cat/indices", "200 OK",
+             r#"[{"health": "green", "status": "open", "index": "foo-2018.10.01", "store.size": "40b"},
+                 {"health": "green", "status": "open", "index": "bar-2018.10.01", "store.size": "40b"}]"#),
+            ("GET /_cat/aliases", "200 OK", "[]"),
+            ("POST /foo-2018.10.01/_close", "200 OK", "{}"),
+            ("POST /bar-2018.10.01/_close", "200 OK", "{}"),
+        ]);
+        let mut config = local(port);
+        for name in &["foo", "bar"] {
+            config.policies.insert(name.to_string(), Policy { close: Some(Stage { after: 5 }), ..Policy::default() });
+        }
+        let cproc = on(&config, 2018, 10, 20);
+
+        cproc.apply(false).unwrap();
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.iter().filter(|r| r.starts_with("GET /_cat/aliases")).count(), 1);
+        assert_eq!(requests.iter().filter(|r| r.ends_with("/_close HTTP/1.1")).count(), 2);
+    }
+
+    #[test]
+    fn failed_deletes_are_errors() {
+        let (port, requests) = cluster(vec![
+            ("GET /_cat/indices", "200 OK",
+             r#"[{"health": "green", "status": "open", "index": "foo-2018.10.01", "store.size": "40b"}]"#),
+            ("GET /_cat/aliases", "200 OK", "[]"),
+            ("DELETE /foo-2018.10.01", "403 Forbidden", "{}"),
+        ]);
+        let config = local(port);
+        let cproc = on(&config, 2018, 10, 20);
+
+        assert!(cproc.delete("foo".into(), &DateRange::all(), None, false).is_err());
+        assert!(requests.lock().unwrap().iter().any(|r| r.starts_with("DELETE /foo-2018.10.01")));
+    }
+
+    fn node(used: u64, total: u64, indices: u64) -> NodeUsage {
+        NodeUsage { name: "node".to_string(), used, total, indices }
+    }
+
+    #[test]
+    fn projected_percent_frees_share_of_deleted_bytes() {
+        let nodes = vec![node(90, 100, 60), node(50, 100, 20)];
+        // the first node holds 3/4 of the index data
+        assert_eq!(max_percent(&nodes, 0, 80), 90.0);
+        assert_eq!(max_percent(&nodes, 40, 80), 60.0);
+    }
+
+    #[test]
+    fn projected_percent_never_goes_negative() {
+        let nodes = vec![node(10, 100, 10)];
+        assert_eq!(max_percent(&nodes, 1000, 10), 0.0);
+    }
+
+    #[test]
+    fn indices_age_once_the_grace_window_passes() {
+        let mut config = Config::new("localhost", "9200", "60");
+        config.grace_hours = 2;
+        let idx = Index::new("foo", 2018, 10, 2);
+        let at = |hour| {
+            let now = NaiveDate::from_ymd_opt(2018, 10, 3).unwrap().and_hms_opt(hour, 30, 0).unwrap();
+            CmdProcessor::with_clock(&config, Box::new(FixedClock::new(DateTime::from_naive_utc_and_offset(now, Utc)))).unwrap()
+                .age(&idx)
+        };
+        assert_eq!(at(1), 0);
+        assert_eq!(at(2), 1);
+    }
+
+    #[test]
+    fn age_and_matching_follow_the_clock() {
+        let config = Config::new("localhost", "9200", "60");
+        let date = NaiveDate::from_ymd_opt(2018, 10, 12).unwrap();
+        let cproc = CmdProcessor::with_clock(&config, Box::new(FixedClock::on(date))).unwrap();
+        let idx = Index::new("foo", 2018, 10, 2);
+        assert_eq!(cproc.age(&idx), 10);
+
+        let range = DateRange::from_offsets(None, Some(9));
+        assert!(matches(&idx, &None, &range, cproc.today()));
+        assert!(!matches(&idx, &None, &DateRange::from_offsets(None, Some(10)), cproc.today()));
+    }
+}
@@ -1,9 +1,17 @@
 //! cmds.rs
 //!
 //! Convenience struct which defines methods for the cli
-use crate::cmdprocessor::CmdProcessor;
+use crate::cmdprocessor::{CmdProcessor, Deletion};
+use crate::daterange::DateRange;
 use crate::errors::EcError;
+use crate::holds::Hold;
+use crate::index::Index;
 use crate::indices::Deprecate;
+use crate::namefilter::NameFilter;
+use crate::rawindex::RawIndex;
+use crate::report::{self, IndexRow, OutputFormat, Summary};
+use crate::size::format_size;
+use crate::size::parse_size;
 use crate::traits::ElasticIndex;
 
 /// Provides cli command methods
@@ -34,10 +42,10 @@ impl<'a, 'b> Cmds<'a, 'b> {
     ///
     /// # Arguments
     ///
-    /// * `name`       - An optional string which is the name of the index we want to query
-    /// * `start`      - An optional start offset, in days, from today
-    /// * `end`        - An optional end offset, in days, from today
+    /// * `name`       - An optional filter on the base names of the indices we want to query
+    /// * `range`      - The dates the indices must lie within
     /// * `names_only` - Whether to query the names of the indices or a specific name
+    /// * `format`     - The shape of the output
     ///
     /// # Returns
     ///
@@ -45,14 +53,39 @@ impl<'a, 'b> Cmds<'a, 'b> {
     /// * `EcError` when unsuccessful
     // TODO: the call is a bit odd. We could change this to multiple methods (query_index, index_names)
     // or we could take an enum QueryParams { Range{name,start,end}, Names }
-    pub fn query(&self, name: Option<String>, start: Option<i32>, end: Option<i32>, names_only:bool)
+    pub fn query(&self, name: Option<NameFilter>, range: &DateRange, names_only:bool, format: OutputFormat)
     -> Result<(), EcError> {
 
-        let  results = if names_only {
-            self.processor.query_names(name, start, end)?
-        } else {
-            self.processor.query(name, start, end)?
-        };
+        if names_only {
+            let results = self.processor.query_names(name, range)?;
+            match format {
+                OutputFormat::Json => {
+                    println!("{}", report::to_json(&json!({ "basenames": results }))?);
+                },
+                OutputFormat::Csv | OutputFormat::Table => {
+                    println!("basename");
+                    for r in &results {
+                        println!("{}", r);
+                    }
+                },
+                OutputFormat::Text => {
+                    for r in &results {
+                        println!("{}", r);
+                    }
+                    println!("Number of Indices: {}",results.len());
+                },
+            }
+            return Ok(());
+        }
+
+        if format != OutputFormat::Text {
+            let mut indices = self.processor.get_raw_indices(name, range)?;
+            indices.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+            let rows = self.rows(&indices)?;
+            return self.print_rows(&rows, format, json!({}));
+        }
+
+        let results = self.processor.query(name, range)?;
 
         for r in &results {
                 println!("{}", r);
@@ -63,6 +96,75 @@ impl<'a, 'b> Cmds<'a, 'b> {
         Ok(())
     }
 
+    /// Roll up the indices matching criteria by basename and print out, for
+    /// each basename, the number of indices, the oldest and newest dates,
+    /// the days missing between them, the total size, and the worst health.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`   - An optional filter on the base names of the indices
+    /// * `range`  - The dates the indices must lie within
+    /// * `format` - The shape of the output. text and table both print a
+    ///   table; json also lists each gap
+    ///
+    /// # Returns
+    ///
+    /// * `()` when successful
+    /// * `EcError` when unsuccessful
+    pub fn summary(&self, name: Option<NameFilter>, range: &DateRange, format: OutputFormat)
+    -> Result<(), EcError> {
+
+        let summaries = self.processor.summarize(name, range)?;
+
+        match format {
+            OutputFormat::Json => {
+                println!("{}", report::to_json(&json!({ "basenames": summaries }))?);
+            },
+            OutputFormat::Csv => print!("{}", report::summaries_to_csv(&summaries)),
+            OutputFormat::Table | OutputFormat::Text => {
+                print!("{}", report::summaries_to_table(&summaries));
+                println!("Number of Basenames: {}  Indices: {}  Store Size: {}",
+                    summaries.len(),
+                    summaries.iter().map(|s| s.indices).sum::<usize>(),
+                    format_size(summaries.iter().map(|s| s.store_bytes).sum()));
+            },
+        }
+
+        Ok(())
+    }
+
+    /// The audit command checks the dates of the indices of each basename,
+    /// and prints out the days on which no index was written, any indices
+    /// dated in the future, and any day covered by more than one index.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`   - An optional filter on the base names of the indices
+    /// * `format` - The shape of the output
+    ///
+    /// # Returns
+    ///
+    /// * The number of problems found, when successful
+    /// * `EcError` when unsuccessful
+    pub fn audit(&self, name: Option<NameFilter>, format: OutputFormat) -> Result<usize, EcError> {
+        let findings = self.processor.audit(name)?;
+
+        match format {
+            OutputFormat::Json => {
+                println!("{}", report::to_json(&json!({ "findings": findings, "count": findings.len() }))?);
+            },
+            OutputFormat::Csv => print!("{}", report::findings_to_csv(&findings)),
+            OutputFormat::Table | OutputFormat::Text => {
+                for finding in &findings {
+                    println!("{}", finding);
+                }
+                println!("Number of Findings: {}", findings.len());
+            },
+        }
+
+        Ok(findings.len())
+    }
+
     /// The process subcommand retrieves an index, optionally with a start and
     /// end offset, and matches its name against known indices. If the index is
     /// known to the system, it retrieves values, possibly within the optional
@@ -71,9 +173,12 @@ impl<'a, 'b> Cmds<'a, 'b> {
     ///
     /// # Arguments
     ///
-    /// * `name`  - The name of the index
-    /// * `start` - Optional offset start time, in days
-    /// * `end`   - Optional offset end time for the query, in days
+    /// * `name`   - The name of the index
+    /// * `start`  - Optional offset start time, in days
+    /// * `end`    - Optional offset end time for the query, in days
+    /// * `format` - The shape of the output. json holds the documents
+    ///   along with the indices they came from; csv and table list only
+    ///   the indices, as the documents are not tabular
     ///
     /// Note: start must be greater than end if provided
     ///
@@ -81,39 +186,315 @@ impl<'a, 'b> Cmds<'a, 'b> {
     ///
     /// * `()` when successful
     /// * `EcError` when unsuccessful
-    pub fn process(&self, name: String, start: Option<i32>, end: Option<i32>)
+    pub fn process(&self, name: String, start: Option<i32>, end: Option<i32>, format: OutputFormat)
     -> Result<(), EcError> {
 
-        let results = match name.as_str() {
-            Deprecate::NAME => { self.processor.get::<Deprecate>(start, end) },
+        match name.as_str() {
+            Deprecate::NAME => { self.process_index::<Deprecate>(start, end, format) },
             _ => {
                 Err(EcError::ParseError(format!("Unrecognized index: {}", name)))
             }
-        }?;
+        }
+    }
 
-        for r in &results {
-            println!("{}", r);
+    // Retrieve and print the documents of a known index
+    fn process_index<I>(&self, start: Option<i32>, end: Option<i32>, format: OutputFormat)
+    -> Result<(), EcError>
+    where
+        I: ElasticIndex
+    {
+        let results = self.processor.get::<I>(start, end)?;
+
+        if format == OutputFormat::Text {
+            for r in &results {
+                println!("{}", r);
+            }
+            return Ok(());
+        }
+
+        let mut indices = self.processor.get_raw_indices(Some(I::NAME.into()), &DateRange::from_offsets(start, end))?;
+        indices.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        let rows = self.rows(&indices)?;
+        let documents = serde_json::to_value(&results).map_err(|e| EcError::OutputError(format!("{}", e)))?;
+        self.print_rows(&rows, format, json!({ "documents": documents }))
+    }
+    /// The delete command removes the provided index values lying within a date
+    /// range. There is a dry run mode provided as well.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`  - Filter on the base names of the indices
+    /// * `range` - The dates the indices must lie within
+    /// * `snapshot_repo` - Optional repository to snapshot the indices into first
+    /// * `format` - The shape of the output
+    ///
+    /// # Returns
+    ///
+    /// * `()` when success
+    /// * `EcError` when unsuccessful
+    pub fn delete(&self, name: NameFilter, range: &DateRange, snapshot_repo: Option<&str>, dry_run: bool,
+                  format: OutputFormat)
+    -> Result<(), EcError> {
+
+        let deletion = self.processor.delete(name, range, snapshot_repo, dry_run)?;
+        self.print_deletion(&deletion, format)
+    }
+
+    /// The delete_to_size command removes the oldest indices of the matching
+    /// basenames until they fit within a size budget, such as `500gb`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`     - Filter on the base names of the indices
+    /// * `max_size` - The size budget, with an optional unit (b, kb, mb, gb, tb, pb)
+    /// * `snapshot_repo` - Optional repository to snapshot the indices into first
+    /// * `dry_run`  - Report what would be deleted without deleting it
+    /// * `format`   - The shape of the output
+    ///
+    /// # Returns
+    ///
+    /// * `()` when success
+    /// * `EcError` when unsuccessful
+    pub fn delete_to_size(&self, name: NameFilter, max_size: &str, snapshot_repo: Option<&str>, dry_run: bool,
+                          format: OutputFormat)
+    -> Result<(), EcError> {
+
+        let max_bytes = parse_size(max_size)?;
+        let deletion = self.processor.delete_to_size(name, max_bytes, snapshot_repo, dry_run)?;
+        self.print_deletion(&deletion, format)
+    }
+
+    /// The restore command restores dated indices of a basename, optionally
+    /// within a start and end offset, from the snapshots in a repository.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`          - The name of the index
+    /// * `start`         - Optional start offset, from today
+    /// * `end`           - Optional end offset, from today
+    /// * `repo`          - The snapshot repository to restore from
+    /// * `rename_prefix` - Optional prefix to restore the indices under
+    /// * `dry_run`       - Report what would be restored without restoring it
+    ///
+    /// # Returns
+    ///
+    /// * `()` when success
+    /// * `EcError` when unsuccessful
+    pub fn restore(&self, name: String, start: Option<i32>, end: Option<i32>, repo: &str,
+                   rename_prefix: Option<&str>, dry_run: bool)
+    -> Result<(), EcError> {
+
+        self.processor.restore(name, &DateRange::from_offsets(start, end), repo, rename_prefix, dry_run)
+    }
+
+    /// The compact command rolls the daily indices of a basename for a month
+    /// up into a single monthly index, deleting the dailies once the monthly
+    /// index is verified to hold all of their documents.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`    - The name of the index
+    /// * `month`   - The month to compact, as `YYYY.MM`
+    /// * `dry_run` - Report what would be compacted without changing anything
+    ///
+    /// # Returns
+    ///
+    /// * `()` when success
+    /// * `EcError` when unsuccessful
+    pub fn compact(&self, name: String, month: &str, dry_run: bool) -> Result<(), EcError> {
+        self.processor.compact(name, month, dry_run)
+    }
+
+    /// The apply command runs the delete stage of every retention policy
+    /// in the config file, each with its own retention.
+    ///
+    /// # Arguments
+    ///
+    /// * `dry_run` - Report what would be deleted without deleting it
+    ///
+    /// # Returns
+    ///
+    /// * `()` when success
+    /// * `EcError` when unsuccessful
+    pub fn apply(&self, dry_run: bool) -> Result<(), EcError> {
+        self.processor.apply(dry_run)
+    }
+
+    /// The streams command lists each data stream, its backing indices,
+    /// and the backing index it writes to.
+    ///
+    /// # Returns
+    ///
+    /// * `()` when success
+    /// * `EcError` when unsuccessful
+    pub fn streams(&self) -> Result<(), EcError> {
+        let streams = self.processor.streams()?;
+
+        for stream in &streams {
+            println!("{} ({} backing indices, generation {})", stream.name, stream.indices.len(), stream.generation);
+            for backing in &stream.indices {
+                if stream.write_index() == Some(backing.index_name.as_str()) {
+                    println!("    {} (write index)", backing.index_name);
+                } else {
+                    println!("    {}", backing.index_name);
+                }
+            }
+        }
+
+        println!("Number of Streams: {}", streams.len());
+
+        Ok(())
+    }
+
+    /// The protect command records a hold on indices, which no delete will
+    /// remove until the hold is lifted with unprotect.
+    ///
+    /// # Arguments
+    ///
+    /// * `hold` - The hold to record
+    ///
+    /// # Returns
+    ///
+    /// * `()` when success
+    /// * `EcError` when unsuccessful
+    pub fn protect(&self, hold: Hold) -> Result<(), EcError> {
+        let msg = format!("holding {}", hold);
+        self.processor.protect(hold)?;
+        println!("{}", msg);
+        Ok(())
+    }
+
+    /// The unprotect command lifts a hold recorded by protect.
+    ///
+    /// # Arguments
+    ///
+    /// * `hold` - The hold to lift
+    ///
+    /// # Returns
+    ///
+    /// * `()` when success
+    /// * `EcError` when unsuccessful
+    pub fn unprotect(&self, hold: &Hold) -> Result<(), EcError> {
+        self.processor.unprotect(hold)?;
+        println!("released {}", hold);
+        Ok(())
+    }
+
+    /// The holds command lists the holds from the config and the state file.
+    ///
+    /// # Returns
+    ///
+    /// * `()` when success
+    /// * `EcError` when unsuccessful
+    pub fn holds(&self) -> Result<(), EcError> {
+        let holds = self.processor.holds()?;
+
+        for hold in &holds {
+            println!("{}", hold);
         }
 
+        println!("Number of Holds: {}", holds.len());
+
         Ok(())
     }
-    /// The delete command removes the provided index values, optionally, beginning
-    /// at a start offsent, and ending `end` days before now. There is a dry run mode
-    /// provided as well.AsMut
+
+    /// The delete_keep_last command removes all but the newest `keep`
+    /// indices of each matching basename.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`    - Filter on the base names of the indices
+    /// * `keep`    - The number of most recent indices to keep
+    /// * `snapshot_repo` - Optional repository to snapshot the indices into first
+    /// * `dry_run` - Report what would be deleted without deleting it
+    /// * `format`  - The shape of the output
+    ///
+    /// # Returns
+    ///
+    /// * `()` when success
+    /// * `EcError` when unsuccessful
+    pub fn delete_keep_last(&self, name: NameFilter, keep: usize, snapshot_repo: Option<&str>, dry_run: bool,
+                            format: OutputFormat)
+    -> Result<(), EcError> {
+
+        let deletion = self.processor.delete_keep_last(name, keep, snapshot_repo, dry_run)?;
+        self.print_deletion(&deletion, format)
+    }
+
+    /// The watermark command deletes the oldest indices of the configured
+    /// basenames when node disk usage is above the high watermark.
     ///
     /// # Arguments
     ///
-    /// * `name`  - The name of the index
-    /// * `start` - Optional start offset, from today, of the deletion
-    /// * `end`   - The end offset of the deletion
+    /// * `high`    - Optional high watermark percentage, overriding the config
+    /// * `low`     - Optional low watermark percentage, overriding the config
+    /// * `dry_run` - Report what would be deleted without deleting it
     ///
     /// # Returns
     ///
     /// * `()` when success
     /// * `EcError` when unsuccessful
-    pub fn delete(&self, name: String, start: Option<i32>, end: i32, dry_run: bool)
+    pub fn watermark(&self, high: Option<u32>, low: Option<u32>, dry_run: bool)
     -> Result<(), EcError> {
 
-        self.processor.delete(name, start, end, dry_run)
+        let high = high.unwrap_or(self.processor.config.watermark.high);
+        let low = low.unwrap_or(self.processor.config.watermark.low);
+        self.processor.delete_to_watermark(high, low, dry_run)
     }
-}
\ No newline at end of file
+
+    // Turn indices and their metadata into report rows, aged as of today
+    fn rows(&self, indices: &[(Index, RawIndex)]) -> Result<Vec<IndexRow>, EcError> {
+        indices.iter()
+            .map(|(idx, raw)| IndexRow::new(idx, raw, self.processor.age(idx)))
+            .collect()
+    }
+
+    // Print rows in a structured format. In json the rows and their summary
+    // are merged into `extra`, which carries any command specific fields.
+    fn print_rows(&self, rows: &[IndexRow], format: OutputFormat, mut extra: serde_json::Value)
+    -> Result<(), EcError> {
+
+        let summary = Summary::of(rows);
+        match format {
+            OutputFormat::Json => {
+                extra["indices"] = json!(rows);
+                extra["summary"] = json!(summary);
+                println!("{}", report::to_json(&extra)?);
+            },
+            OutputFormat::Csv => print!("{}", report::to_csv(rows)),
+            OutputFormat::Table | OutputFormat::Text => {
+                print!("{}", report::to_table(rows));
+                println!("Number of Indices: {}  Documents: {}  Store Bytes: {}",
+                    summary.indices, summary.docs_count, summary.store_bytes);
+            },
+        }
+        Ok(())
+    }
+
+    // Print the outcome of a delete. The structured formats list the indices
+    // deleted, or to be deleted in dry_run mode; json adds those held back.
+    fn print_deletion(&self, deletion: &Deletion, format: OutputFormat) -> Result<(), EcError> {
+        if format == OutputFormat::Text {
+            deletion.print();
+            return Ok(());
+        }
+
+        let rows = self.rows(&deletion.deleted)?;
+        let held = deletion.held.iter()
+            .map(|(idx, hold)| json!({ "index": idx.to_string(), "hold": hold.to_string() }))
+            .collect::<Vec<serde_json::Value>>();
+        let write_indices = deletion.write_indices.iter()
+            .map(|idx| idx.to_string())
+            .collect::<Vec<String>>();
+        self.print_rows(&rows, format, json!({
+            "dry_run": deletion.dry_run,
+            "snapshot_repo": deletion.snapshot_repo,
+            "held": held,
+            "write_indices": write_indices,
+        }))?;
+        if format == OutputFormat::Table && deletion.dry_run {
+            println!("dry-run");
+        }
+        Ok(())
+    }
+}
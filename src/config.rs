@@ -2,19 +2,156 @@
 //!
 //! Configuration data management
 use crate::{
-    constants::{HOSTVAR, PORTVAR, MIN_DAYS_VAR},
+    clock::Timezone,
+    connection::{AuthSettings, Connection, Scheme, TlsSettings},
+    constants::{HOSTVAR, PORTVAR, URLVAR, SNIFFVAR, MIN_DAYS_VAR, CONFIGVAR, STATEVAR, STATE_FILE, TIMEZONE_VAR, GRACE_HOURS_VAR,
+                MIN_DAYS, HIGH_WATERMARK, LOW_WATERMARK},
     errors::EcError,
+    holds::Hold,
+    pattern::IndexPattern,
  };
-use std::env::var;
+use glob::Pattern;
+use reqwest::Url;
+use std::{
+    collections::BTreeMap,
+    env::var,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Settings for the delete stage of a Policy
+#[derive(Deserialize, PartialEq, Eq, PartialOrd, Ord, Debug, Clone)]
+pub struct DeletePolicy {
+    /// The number of days of indices to keep. Anything older is deleted.
+    pub retain: u32,
+    /// Snapshot repository to snapshot indices into before deleting them
+    pub snapshot_repo: Option<String>,
+}
+
+/// A lifecycle stage which applies to indices older than `after` days
+#[derive(Deserialize, PartialEq, Eq, PartialOrd, Ord, Debug, Clone)]
+pub struct Stage {
+    pub after: u32,
+}
+
+/// Settings for the force merge stage of a Policy
+#[derive(Deserialize, PartialEq, Eq, PartialOrd, Ord, Debug, Clone)]
+pub struct ForceMergeStage {
+    /// Force merge indices older than this many days
+    pub after: u32,
+    /// The number of segments to merge each shard down to
+    #[serde(default = "default_max_num_segments")]
+    pub max_num_segments: u32,
+}
+
+fn default_max_num_segments() -> u32 {
+    1
+}
+
+/// The retention policy for a single index basename, as read from
+/// the config file. The stages are applied in order of increasing severity:
+/// force merge, read only, close, and finally delete. There is deliberately
+/// no freeze stage: frozen indices were deprecated in elasticsearch 7.14 and
+/// removed in 8.0, and closing an index frees its memory just the same.
+#[derive(Deserialize, PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Default)]
+pub struct Policy {
+    /// Naming pattern for the basename's indices (eg `{base}_%Y%m%d`)
+    pub pattern: Option<String>,
+    pub forcemerge: Option<ForceMergeStage>,
+    pub read_only: Option<Stage>,
+    pub close: Option<Stage>,
+    pub delete: Option<DeletePolicy>,
+}
+
+/// Node disk usage percentages which drive the watermark command. Once
+/// any node is above `high`, indices are deleted until usage is projected
+/// to fall below `low`.
+#[derive(Deserialize, PartialEq, Eq, PartialOrd, Ord, Debug, Clone)]
+pub struct Watermark {
+    pub high: u32,
+    pub low: u32,
+}
+
+impl Default for Watermark {
+    fn default() -> Self {
+        Watermark {
+            high: HIGH_WATERMARK,
+            low: LOW_WATERMARK,
+        }
+    }
+}
+
+// Layout of the config file on disk. The connection settings are optional;
+// any which are missing are picked up from the environment instead.
+#[derive(Deserialize, Debug)]
+struct ConfigFile {
+    host: Option<String>,
+    port: Option<u16>,
+    url: Option<String>,
+    #[serde(default)]
+    urls: Vec<String>,
+    sniff: Option<bool>,
+    scheme: Option<String>,
+    #[serde(default)]
+    auth: AuthSettings,
+    #[serde(default)]
+    tls: TlsSettings,
+    min_days: Option<u32>,
+    timezone: Option<String>,
+    grace_hours: Option<u32>,
+    #[serde(default)]
+    floors: BTreeMap<String, u32>,
+    #[serde(default)]
+    patterns: Vec<String>,
+    #[serde(default)]
+    watermark: Watermark,
+    #[serde(default)]
+    indices: BTreeMap<String, Policy>,
+    #[serde(default)]
+    protect: Vec<Hold>,
+    state_file: Option<PathBuf>,
+}
 
 /// Store configurable data, like the elasticsearch host name
 /// and port. The struct provides functions to generate a Config
-/// from environment variables.
-#[derive(PartialEq, Eq, PartialOrd, Ord, Debug)]
+/// from environment variables or from a yaml config file.
+#[derive(PartialEq, Eq, Debug)]
 pub struct Config {
     pub host: String,
     pub port: u16,
+    /// The base urls of the nodes requests are made against, tried in
+    /// order. A single url is built from the scheme, host and port unless
+    /// they are given in full, when each may carry a path prefix (eg
+    /// `https://gateway/es-prod/`). The host and port are those of the first.
+    pub urls: Vec<Url>,
+    /// Whether to ask the cluster for the http addresses of its other nodes
+    /// and fall back on them after the configured urls
+    pub sniff: bool,
+    /// The scheme, credentials, and certificates used to reach elasticsearch
+    pub connection: Connection,
+    /// The default minimum number of days of indices to keep
     pub min_days: u32,
+    /// The timezone index dates are reckoned in. Defaults to UTC, which
+    /// logstash names its indices by.
+    pub timezone: Timezone,
+    /// The number of hours after the end of its period before an index
+    /// starts to age, so that indices which may still be written to are
+    /// left alone
+    pub grace_hours: u32,
+    /// Minimum number of days to keep, keyed by basename or basename
+    /// wildcard pattern (eg `nginx*`). Overrides `min_days`.
+    pub floors: BTreeMap<String, u32>,
+    /// Index naming patterns (eg `{base}_%Y%m%d`) which apply to any basename
+    pub patterns: Vec<String>,
+    /// Disk usage thresholds for the watermark command
+    pub watermark: Watermark,
+    /// Retention policies keyed by index basename
+    pub policies: BTreeMap<String, Policy>,
+    /// Holds on indices which are never deleted, in addition to those in
+    /// the state file
+    pub holds: Vec<Hold>,
+    /// Path to the state file recording the holds added by the protect command
+    pub state_file: PathBuf,
 }
 
 impl Config {
@@ -34,44 +171,495 @@ impl Config {
         J: Into<String>,
         K: Into<String>,
     {
+        let host = host.into();
+        let port = port.into().parse::<u16>().unwrap();
         Config {
-            host: host.into(),
-            port: port.into().parse::<u16>().unwrap(),
+            urls: vec![Url::parse(&format!("http://{}:{}/", host, port)).unwrap()],
+            sniff: false,
+            host,
+            port,
+            connection: Connection::default(),
             min_days: min_days.into().parse::<u32>().unwrap(),
+            timezone: Timezone::default(),
+            grace_hours: 0,
+            floors: BTreeMap::new(),
+            patterns: Vec::new(),
+            watermark: Watermark::default(),
+            policies: BTreeMap::new(),
+            holds: Vec::new(),
+            state_file: default_state_file(),
         }
     }
 
+    /// Look up the minimum number of days of indices to keep for a basename.
+    /// An exact entry in `floors` wins. Otherwise the largest floor of any
+    /// matching wildcard pattern is used, falling back on `min_days` if no
+    /// pattern matches.
+    ///
+    /// # Parameters
+    ///
+    /// * `name` - The base name of the index (sans date)
+    ///
+    /// # Returns
+    ///
+    /// * `u32` minimum number of days to keep
+    pub fn min_days_for(&self, name: &str) -> u32 {
+        if let Some(floor) = self.floors.get(name) {
+            return *floor;
+        }
+
+        self.floors.iter()
+            .filter(|(pattern, _)| {
+                Pattern::new(pattern).map(|p| p.matches(name)).unwrap_or(false)
+            })
+            .map(|(_, floor)| *floor)
+            .max()
+            .unwrap_or(self.min_days)
+    }
+
     /// New up a Config, grabbing the host and port from environment variables.
     ///
     /// # Environment Variables (defined in constants)
     ///
     /// * `ELASTICLEAN_HOST` - Name of the host
     /// * `ELASTICLEAN_PORT` - Port number
+    /// * `ELASTICLEAN_URL`  - The full base url, in place of the host and port,
+    ///   or a comma separated list of them
+    /// * `ELASTICLEAN_SNIFF` - `true` to fall back on the other nodes the
+    ///   cluster reports
+    ///
+    /// along with the scheme, credential and certificate variables read by
+    /// `Connection::from_env`.
     ///
     /// # Returns
     ///
     /// * `Config` instance if successful
     /// * `ExError` instance if failure
     pub fn from_env() -> Result<Config, EcError> {
-        let host = var(HOSTVAR).map_err(|_| { EcError::EnvVarError(HOSTVAR.to_string())} )?;
-        let port = var(PORTVAR).map_err(|_| {EcError::EnvVarError(PORTVAR.to_string())})?;
         let min_days = var(MIN_DAYS_VAR).map_err(|_| {EcError::EnvVarError(MIN_DAYS_VAR.to_string())})?;
-        Ok(Config::new(host, port, min_days))
+        let mut connection = Connection::from_env()?;
+        let (host, port, urls) = locate(urls_from_env(), var(HOSTVAR).ok(), port_from_env()?, &mut connection)?;
+        let mut config = Config::new(host, port.to_string(), min_days);
+        config.urls = urls;
+        config.sniff = sniff_from_env()?.unwrap_or(false);
+        config.connection = connection;
+        config.timezone = timezone_from_env()?.unwrap_or_default();
+        config.grace_hours = grace_hours_from_env()?.unwrap_or(0);
+        Ok(config)
+    }
+
+    /// New up a Config from a yaml file. The file holds the per basename
+    /// retention policies, and may optionally set `host`, `port`, and `min_days`.
+    /// Any of those which are absent fall back to the environment variables
+    /// used by `from_env`, with `min_days` defaulting to `MIN_DAYS`.
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - Path to the yaml config file
+    ///
+    /// # Returns
+    ///
+    /// * `Config` instance if successful
+    /// * `EcError` instance if failure
+    pub fn from_file<P>(path: P) -> Result<Config, EcError>
+    where
+        P: AsRef<Path>
+    {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)
+            .map_err(|e| EcError::ConfigReadError(format!("{}: {}", path.display(), e)))?;
+        Config::from_yaml(&contents)
+    }
+
+    /// New up a Config from the file named by `ELASTICLEAN_CONFIG` if it is
+    /// set, falling back on `from_env` otherwise.
+    ///
+    /// # Returns
+    ///
+    /// * `Config` instance if successful
+    /// * `EcError` instance if failure
+    pub fn load() -> Result<Config, EcError> {
+        match var(CONFIGVAR) {
+            Ok(path) => Config::from_file(path),
+            Err(_) => Config::from_env(),
+        }
+    }
+
+    // Build a Config from the contents of a yaml config file
+    fn from_yaml(contents: &str) -> Result<Config, EcError> {
+        let file: ConfigFile = serde_yaml::from_str(contents)
+            .map_err(|e| EcError::ConfigParseError(format!("{}", e)))?;
+
+        let mut connection = Connection::resolve(file.scheme, file.auth, file.tls, &|name| var(name).ok())?;
+        let port = match file.port {
+            Some(port) => Some(port),
+            None => port_from_env()?,
+        };
+        let mut urls = file.url.into_iter().chain(file.urls).collect::<Vec<String>>();
+        if urls.is_empty() {
+            urls = urls_from_env();
+        }
+        let (host, port, urls) = locate(urls,
+                                       file.host.or_else(|| var(HOSTVAR).ok()),
+                                       port,
+                                       &mut connection)?;
+        let min_days = match file.min_days {
+            Some(min_days) => min_days,
+            None => match var(MIN_DAYS_VAR) {
+                Ok(v) => v.parse::<u32>()
+                         .map_err(|e| EcError::ConfigParseError(format!("{}: {}", MIN_DAYS_VAR, e)))?,
+                Err(_) => MIN_DAYS as u32,
+            },
+        };
+
+        let timezone = match file.timezone {
+            Some(timezone) => timezone.parse::<Timezone>()
+                              .map_err(|e| EcError::ConfigParseError(format!("{}", e)))?,
+            None => timezone_from_env()?.unwrap_or_default(),
+        };
+        let grace_hours = match file.grace_hours {
+            Some(grace_hours) => grace_hours,
+            None => grace_hours_from_env()?.unwrap_or(0),
+        };
+        let sniff = match file.sniff {
+            Some(sniff) => sniff,
+            None => sniff_from_env()?.unwrap_or(false),
+        };
+
+        if file.watermark.low > file.watermark.high {
+            return Err(EcError::ConfigParseError(
+                format!("watermark low {} is above high {}", file.watermark.low, file.watermark.high)));
+        }
+
+        for pattern in file.floors.keys() {
+            Pattern::new(pattern)
+                .map_err(|e| EcError::ConfigParseError(format!("floor pattern {}: {}", pattern, e)))?;
+        }
+
+        for hold in &file.protect {
+            hold.validate().map_err(|e| EcError::ConfigParseError(format!("{}", e)))?;
+        }
+
+        for pattern in &file.patterns {
+            IndexPattern::new(pattern, None)
+                .map_err(|e| EcError::ConfigParseError(format!("{}", e)))?;
+        }
+        for (name, policy) in &file.indices {
+            if let Some(ref pattern) = policy.pattern {
+                IndexPattern::new(pattern, Some(name))
+                    .map_err(|e| EcError::ConfigParseError(format!("{}", e)))?;
+            }
+        }
+
+        Ok(Config {
+            host,
+            port,
+            urls,
+            sniff,
+            connection,
+            min_days,
+            timezone,
+            grace_hours,
+            floors: file.floors,
+            patterns: file.patterns,
+            watermark: file.watermark,
+            policies: file.indices,
+            holds: file.protect,
+            state_file: file.state_file.unwrap_or_else(default_state_file),
+        })
+    }
+}
+
+// The optional port named by `ELASTICLEAN_PORT`
+fn port_from_env() -> Result<Option<u16>, EcError> {
+    match var(PORTVAR) {
+        Ok(v) => v.parse::<u16>()
+                 .map(Some)
+                 .map_err(|e| EcError::ConfigParseError(format!("{}: {}", PORTVAR, e))),
+        Err(_) => Ok(None),
+    }
+}
+
+// The optional urls named by `ELASTICLEAN_URL`, separated by commas
+fn urls_from_env() -> Vec<String> {
+    match var(URLVAR) {
+        Ok(v) => v.split(',')
+                 .map(|url| url.trim())
+                 .filter(|url| !url.is_empty())
+                 .map(String::from)
+                 .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+// Whether `ELASTICLEAN_SNIFF` asks for the cluster's nodes to be sniffed
+fn sniff_from_env() -> Result<Option<bool>, EcError> {
+    match var(SNIFFVAR) {
+        Ok(v) => v.parse::<bool>()
+                 .map(Some)
+                 .map_err(|e| EcError::ConfigParseError(format!("{}: {}", SNIFFVAR, e))),
+        Err(_) => Ok(None),
+    }
+}
+
+// Work out the host, port and base urls of elasticsearch. Full urls win
+// over the host and port, and the first url's scheme over the connection's.
+// Otherwise both the host and port are required.
+fn locate(urls: Vec<String>, host: Option<String>, port: Option<u16>, connection: &mut Connection)
+-> Result<(String, u16, Vec<Url>), EcError> {
+    if !urls.is_empty() {
+        let urls = urls.iter()
+            .map(|url| parse_url(url))
+            .collect::<Result<Vec<Url>, EcError>>()?;
+        connection.scheme = urls[0].scheme().parse::<Scheme>()?;
+        let host = urls[0].host_str()
+            .ok_or_else(|| EcError::ConfigParseError(format!("url {} has no host", urls[0])))?
+            .to_string();
+        let port = urls[0].port_or_known_default().unwrap_or(80);
+        return Ok((host, port, urls));
+    }
+
+    let host = host.ok_or_else(|| EcError::EnvVarError(HOSTVAR.to_string()))?;
+    let port = port.ok_or_else(|| EcError::EnvVarError(PORTVAR.to_string()))?;
+    let url = parse_url(&format!("{}://{}:{}/", connection.scheme, host, port))?;
+    Ok((host, port, vec![url]))
+}
+
+// Parse the base url of a node, which must be http or https
+fn parse_url(url: &str) -> Result<Url, EcError> {
+    let parsed = Url::parse(url).map_err(|e| EcError::ConfigParseError(format!("url {}: {}", url, e)))?;
+    parsed.scheme().parse::<Scheme>()?;
+    Ok(parsed)
+}
+
+// The optional timezone named by `ELASTICLEAN_TIMEZONE`
+fn timezone_from_env() -> Result<Option<Timezone>, EcError> {
+    match var(TIMEZONE_VAR) {
+        Ok(v) => v.parse::<Timezone>()
+                 .map(Some)
+                 .map_err(|e| EcError::ConfigParseError(format!("{}: {}", TIMEZONE_VAR, e))),
+        Err(_) => Ok(None),
+    }
+}
+
+// The optional grace window named by `ELASTICLEAN_GRACE_HOURS`
+fn grace_hours_from_env() -> Result<Option<u32>, EcError> {
+    match var(GRACE_HOURS_VAR) {
+        Ok(v) => v.parse::<u32>()
+                 .map(Some)
+                 .map_err(|e| EcError::ConfigParseError(format!("{}: {}", GRACE_HOURS_VAR, e))),
+        Err(_) => Ok(None),
+    }
+}
+
+// The state file named by `ELASTICLEAN_STATE`, falling back on `STATE_FILE`
+// in the home directory, or in the working directory if there is no home
+fn default_state_file() -> PathBuf {
+    match (var(STATEVAR), var("HOME")) {
+        (Ok(path), _) => PathBuf::from(path),
+        (Err(_), Ok(home)) => Path::new(&home).join(STATE_FILE),
+        (Err(_), Err(_)) => PathBuf::from(STATE_FILE),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::connection::Credentials;
+    use chrono::naive::NaiveDate;
     #[test]
     fn can_new_up_config() {
         let nc = Config::new("foo", "16", "5");
         let expect = Config {
             host: "foo".to_string(),
             port: 16,
+            urls: vec![Url::parse("http://foo:16/").unwrap()],
+            sniff: false,
+            connection: Connection::default(),
             min_days: 5,
+            timezone: Timezone::Utc,
+            grace_hours: 0,
+            floors: BTreeMap::new(),
+            patterns: Vec::new(),
+            watermark: Watermark::default(),
+            policies: BTreeMap::new(),
+            holds: Vec::new(),
+            state_file: default_state_file(),
         };
 
         assert_eq!(nc, expect);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn can_parse_config_from_yaml() {
+        let yaml = r#"
+host: foo
+port: 9200
+min_days: 30
+indices:
+  logstash:
+    delete:
+      retain: 60
+  exceptions:
+    forcemerge:
+      after: 7
+    read_only:
+      after: 30
+    close:
+      after: 60
+    delete:
+      retain: 90
+      snapshot_repo: backups
+"#;
+        let nc = Config::from_yaml(yaml).unwrap();
+        let mut policies = BTreeMap::new();
+        policies.insert("logstash".to_string(), Policy { delete: Some(DeletePolicy { retain: 60, snapshot_repo: None }), ..Policy::default() });
+        policies.insert("exceptions".to_string(), Policy {
+            pattern: None,
+            forcemerge: Some(ForceMergeStage { after: 7, max_num_segments: 1 }),
+            read_only: Some(Stage { after: 30 }),
+            close: Some(Stage { after: 60 }),
+            delete: Some(DeletePolicy { retain: 90, snapshot_repo: Some("backups".to_string()) }),
+        });
+        let expect = Config {
+            host: "foo".to_string(),
+            port: 9200,
+            urls: vec![Url::parse("http://foo:9200/").unwrap()],
+            sniff: false,
+            connection: Connection::default(),
+            min_days: 30,
+            timezone: Timezone::Utc,
+            grace_hours: 0,
+            floors: BTreeMap::new(),
+            patterns: Vec::new(),
+            watermark: Watermark::default(),
+            policies,
+            holds: Vec::new(),
+            state_file: default_state_file(),
+        };
+
+        assert_eq!(nc, expect);
+    }
+
+    #[test]
+    fn malformed_yaml_produces_error() {
+        let nc = Config::from_yaml("host: foo\nport: 9200\nindices:\n  logstash:\n    delete: 60\n");
+        assert!(nc.is_err());
+    }
+
+    #[test]
+    fn min_days_for_prefers_exact_then_pattern_then_default() {
+        let yaml = r#"
+host: foo
+port: 9200
+min_days: 60
+floors:
+  audit: 365
+  "nginx*": 14
+  "nginx-error*": 30
+"#;
+        let nc = Config::from_yaml(yaml).unwrap();
+        assert_eq!(nc.min_days_for("audit"), 365);
+        assert_eq!(nc.min_days_for("nginx-access"), 14);
+        assert_eq!(nc.min_days_for("nginx-error"), 30);
+        assert_eq!(nc.min_days_for("logstash"), 60);
+    }
+
+    #[test]
+    fn can_parse_naming_patterns() {
+        let yaml = r#"
+host: foo
+port: 9200
+patterns:
+  - "{base}_%Y%m%d"
+indices:
+  legacy:
+    pattern: "{base}-%Y-%m-%d"
+"#;
+        let nc = Config::from_yaml(yaml).unwrap();
+        assert_eq!(nc.patterns, vec!["{base}_%Y%m%d".to_string()]);
+        assert_eq!(nc.policies["legacy"].pattern, Some("{base}-%Y-%m-%d".to_string()));
+    }
+
+    #[test]
+    fn invalid_naming_pattern_produces_error() {
+        let nc = Config::from_yaml("host: foo\nport: 9200\npatterns:\n  - \"%Y%m%d\"\n");
+        assert!(nc.is_err());
+    }
+
+    #[test]
+    fn can_parse_connection_settings() {
+        let nc = Config::from_yaml("host: foo\nport: 9200\nscheme: https\nauth:\n  api_key: abc\n").unwrap();
+        assert_eq!(nc.connection.scheme, Scheme::Https);
+        assert_eq!(nc.connection.credentials, Credentials::ApiKey("abc".into()));
+        assert!(Config::from_yaml("host: foo\nport: 9200\nscheme: gopher\n").is_err());
+    }
+
+    #[test]
+    fn url_with_path_prefix_wins_over_host_and_port() {
+        let nc = Config::from_yaml("host: foo\nport: 9200\nurl: https://gateway/es-prod/\n").unwrap();
+        assert_eq!(nc.urls[0].as_str(), "https://gateway/es-prod/");
+        assert_eq!(nc.host, "gateway");
+        assert_eq!(nc.port, 443);
+        assert_eq!(nc.connection.scheme, Scheme::Https);
+        assert!(Config::from_yaml("url: ftp://gateway/\n").is_err());
+        assert!(Config::from_yaml("url: not a url\n").is_err());
+    }
+
+    #[test]
+    fn https_scheme_builds_https_url() {
+        let nc = Config::from_yaml("host: foo\nport: 9200\nscheme: https\n").unwrap();
+        assert_eq!(nc.urls[0].as_str(), "https://foo:9200/");
+    }
+
+    #[test]
+    fn can_list_several_node_urls() {
+        let yaml = "url: http://client-01:9200/\nurls:\n  - http://client-02:9200/\n  - http://client-03:9200/\nsniff: true\n";
+        let nc = Config::from_yaml(yaml).unwrap();
+        let urls = nc.urls.iter().map(|url| url.as_str()).collect::<Vec<&str>>();
+        assert_eq!(urls, vec!["http://client-01:9200/", "http://client-02:9200/", "http://client-03:9200/"]);
+        assert_eq!(nc.host, "client-01");
+        assert!(nc.sniff);
+        assert!(Config::from_yaml("urls:\n  - http://client-01:9200/\n  - ftp://client-02/\n").is_err());
+    }
+
+    #[test]
+    fn can_parse_timezone_and_grace_hours() {
+        let nc = Config::from_yaml("host: foo\nport: 9200\ntimezone: \"-05:00\"\ngrace_hours: 2\n").unwrap();
+        assert_eq!(nc.timezone, "-05:00".parse::<Timezone>().unwrap());
+        assert_eq!(nc.grace_hours, 2);
+        assert!(Config::from_yaml("host: foo\nport: 9200\ntimezone: nowhere\n").is_err());
+    }
+
+    #[test]
+    fn can_parse_holds() {
+        let yaml = r#"
+host: foo
+port: 9200
+state_file: /var/lib/elasticlean/holds.json
+protect:
+  - index: audit-2018.10.02
+    reason: legal hold
+  - basename: "nginx*"
+    from: 2018-10-01
+    to: 2018-10-31
+"#;
+        let nc = Config::from_yaml(yaml).unwrap();
+        assert_eq!(nc.state_file, PathBuf::from("/var/lib/elasticlean/holds.json"));
+        assert_eq!(nc.holds.len(), 2);
+        assert_eq!(nc.holds[0].reason, Some("legal hold".to_string()));
+        assert_eq!(nc.holds[1].from, NaiveDate::from_ymd_opt(2018, 10, 1));
+    }
+
+    #[test]
+    fn invalid_hold_produces_error() {
+        let nc = Config::from_yaml("host: foo\nport: 9200\nprotect:\n  - reason: nothing held\n");
+        assert!(nc.is_err());
+    }
+
+    #[test]
+    fn invalid_floor_pattern_produces_error() {
+        let nc = Config::from_yaml("host: foo\nport: 9200\nfloors:\n  \"nginx[\": 14\n");
+        assert!(nc.is_err());
+    }
+}
@@ -0,0 +1,322 @@
+//! # connection.rs
+//!
+//! How elasticlean reaches and authenticates to elasticsearch: the scheme,
+//! the credentials sent with every request, and the certificates used to
+//! verify the cluster and to identify ourselves to it. Each setting comes
+//! from the config file, or failing that from the environment, and every
+//! secret may be read from a file rather than given inline.
+use crate::constants::{SCHEMEVAR, USERNAME_VAR, PASSWORD_VAR, API_KEY_VAR, BEARER_TOKEN_VAR, CA_CERT_VAR,
+                       CLIENT_CERT_VAR, CLIENT_CERT_PASSWORD_VAR};
+use crate::errors::EcError;
+use reqwest::{header::AUTHORIZATION, Certificate, Client, Identity, RequestBuilder};
+use std::{
+    env::var,
+    fmt,
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// The scheme used to reach elasticsearch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Scheme {
+    #[default]
+    Http,
+    Https,
+}
+
+impl FromStr for Scheme {
+    type Err = EcError;
+
+    fn from_str(input: &str) -> Result<Scheme, EcError> {
+        match input.to_lowercase().as_str() {
+            "http" => Ok(Scheme::Http),
+            "https" => Ok(Scheme::Https),
+            _ => Err(EcError::ConfigParseError(format!("unknown scheme {}. expected http or https", input))),
+        }
+    }
+}
+
+impl fmt::Display for Scheme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Scheme::Http => write!(f, "http"),
+            Scheme::Https => write!(f, "https"),
+        }
+    }
+}
+
+/// A password, key or token, which is kept out of debug output
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    /// The secret itself
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(secret: &str) -> Secret {
+        Secret(secret.to_string())
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Secret(****)")
+    }
+}
+
+/// The credentials sent with every request
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Credentials {
+    #[default]
+    None,
+    /// http basic auth
+    Basic { username: String, password: Option<Secret> },
+    /// An elasticsearch api key, base64 encoded as `id:api_key`, as the
+    /// create api key api returns it in `encoded`
+    ApiKey(Secret),
+    /// An oauth or service account token
+    Bearer(Secret),
+}
+
+/// The `auth` section of the config file. Each secret may be given inline
+/// or as the path to a file holding it.
+#[derive(Deserialize, Debug, Default)]
+pub(crate) struct AuthSettings {
+    username: Option<String>,
+    password: Option<String>,
+    password_file: Option<PathBuf>,
+    api_key: Option<String>,
+    api_key_file: Option<PathBuf>,
+    bearer_token: Option<String>,
+    bearer_token_file: Option<PathBuf>,
+}
+
+/// The `tls` section of the config file
+#[derive(Deserialize, Debug, Default)]
+pub(crate) struct TlsSettings {
+    ca_cert: Option<PathBuf>,
+    client_cert: Option<PathBuf>,
+    client_cert_password: Option<String>,
+    client_cert_password_file: Option<PathBuf>,
+}
+
+/// Everything needed to reach and authenticate to elasticsearch, other
+/// than its host and port
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Connection {
+    pub scheme: Scheme,
+    pub credentials: Credentials,
+    /// A PEM bundle of CA certificates to trust, in addition to the system's
+    pub ca_cert: Option<PathBuf>,
+    /// A PKCS #12 (.p12 or .pfx) client certificate and key to present
+    pub client_cert: Option<PathBuf>,
+    pub client_cert_password: Option<Secret>,
+}
+
+impl Connection {
+    /// New up a Connection from the environment variables alone.
+    ///
+    /// # Environment Variables (defined in constants)
+    ///
+    /// * `ELASTICLEAN_SCHEME` - http or https
+    /// * `ELASTICLEAN_USERNAME` and `ELASTICLEAN_PASSWORD` - Basic auth
+    /// * `ELASTICLEAN_API_KEY` - An encoded api key
+    /// * `ELASTICLEAN_BEARER_TOKEN` - A bearer token
+    /// * `ELASTICLEAN_CA_CERT` - Path to a PEM CA bundle
+    /// * `ELASTICLEAN_CLIENT_CERT` and `ELASTICLEAN_CLIENT_CERT_PASSWORD` -
+    ///   Path to a PKCS #12 client certificate, and its password
+    ///
+    /// Each secret may instead be read from the file named by the variable
+    /// with a `_FILE` suffix.
+    ///
+    /// # Returns
+    ///
+    /// * `Connection` instance if successful
+    /// * `EcError` if the settings conflict or a secret cannot be read
+    pub fn from_env() -> Result<Connection, EcError> {
+        Connection::resolve(None, AuthSettings::default(), TlsSettings::default(), &|name| var(name).ok())
+    }
+
+    // Resolve the settings from the config file, falling back on the
+    // environment, as looked up by `env`, for any which are absent. Naming
+    // a certificate without a scheme implies https.
+    pub(crate) fn resolve(scheme: Option<String>, auth: AuthSettings, tls: TlsSettings,
+                          env: &dyn Fn(&str) -> Option<String>)
+    -> Result<Connection, EcError> {
+
+        let username = auth.username.or_else(|| env(USERNAME_VAR));
+        let password = secret(auth.password, auth.password_file, PASSWORD_VAR, env)?;
+        let api_key = secret(auth.api_key, auth.api_key_file, API_KEY_VAR, env)?;
+        let bearer_token = secret(auth.bearer_token, auth.bearer_token_file, BEARER_TOKEN_VAR, env)?;
+
+        let credentials = match (username, password, api_key, bearer_token) {
+            (None, None, None, None) => Credentials::None,
+            (Some(username), password, None, None) => Credentials::Basic { username, password },
+            (None, Some(_), None, None) =>
+                return Err(EcError::ConfigParseError("a password was given without a username".to_string())),
+            (None, None, Some(key), None) => Credentials::ApiKey(key),
+            (None, None, None, Some(token)) => Credentials::Bearer(token),
+            _ => return Err(EcError::ConfigParseError(
+                "only one of a username and password, an api key, or a bearer token may be given".to_string())),
+        };
+
+        let ca_cert = tls.ca_cert.or_else(|| env(CA_CERT_VAR).map(PathBuf::from));
+        let client_cert = tls.client_cert.or_else(|| env(CLIENT_CERT_VAR).map(PathBuf::from));
+        let client_cert_password = secret(tls.client_cert_password, tls.client_cert_password_file,
+                                          CLIENT_CERT_PASSWORD_VAR, env)?;
+
+        let scheme = match scheme.or_else(|| env(SCHEMEVAR)) {
+            Some(scheme) => scheme.parse::<Scheme>()?,
+            None if ca_cert.is_some() || client_cert.is_some() => Scheme::Https,
+            None => Scheme::Http,
+        };
+
+        Ok(Connection { scheme, credentials, ca_cert, client_cert, client_cert_password })
+    }
+
+    /// Build an http client which trusts the CA bundle and presents the
+    /// client certificate, if either is set.
+    ///
+    /// # Returns
+    ///
+    /// * `reqwest::Client` if successful
+    /// * `EcError` if a certificate cannot be read or is invalid
+    pub fn client(&self) -> Result<Client, EcError> {
+        let mut builder = Client::builder();
+        if let Some(ref path) = self.ca_cert {
+            let pem = read(path)?;
+            let cert = Certificate::from_pem(&pem)
+                .map_err(|e| EcError::ConnectionError(format!("ca certificate {}: {}", path.display(), e)))?;
+            builder = builder.add_root_certificate(cert);
+        }
+        if let Some(ref path) = self.client_cert {
+            let der = read(path)?;
+            let password = self.client_cert_password.as_ref().map(Secret::expose).unwrap_or("");
+            let identity = Identity::from_pkcs12_der(&der, password)
+                .map_err(|e| EcError::ConnectionError(format!("client certificate {}: {}", path.display(), e)))?;
+            builder = builder.identity(identity);
+        }
+        builder.build().map_err(|e| EcError::ConnectionError(format!("{}", e)))
+    }
+
+    /// Add the credentials to a request
+    pub fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        match self.credentials {
+            Credentials::None => request,
+            Credentials::Basic { ref username, ref password } =>
+                request.basic_auth(username, password.as_ref().map(Secret::expose)),
+            Credentials::ApiKey(ref key) => request.header(AUTHORIZATION, format!("ApiKey {}", key.expose())),
+            Credentials::Bearer(ref token) => request.bearer_auth(token.expose()),
+        }
+    }
+}
+
+// Read a file named in the settings
+fn read(path: &Path) -> Result<Vec<u8>, EcError> {
+    fs::read(path).map_err(|e| EcError::ConfigReadError(format!("{}: {}", path.display(), e)))
+}
+
+// Resolve a secret from, in order, its inline config value, the file named
+// in the config, the environment variable `name`, and the file named by the
+// environment variable `name`_FILE. Trailing line breaks are dropped from
+// secrets read from files.
+fn secret(value: Option<String>, file: Option<PathBuf>, name: &str, env: &dyn Fn(&str) -> Option<String>)
+-> Result<Option<Secret>, EcError> {
+    let file = match (value, file) {
+        (Some(value), _) => return Ok(Some(Secret(value))),
+        (None, Some(file)) => file,
+        (None, None) => match (env(name), env(&format!("{}_FILE", name))) {
+            (Some(value), _) => return Ok(Some(Secret(value))),
+            (None, Some(file)) => PathBuf::from(file),
+            (None, None) => return Ok(None),
+        },
+    };
+    let contents = String::from_utf8(read(&file)?)
+        .map_err(|e| EcError::ConfigReadError(format!("{}: {}", file.display(), e)))?;
+    Ok(Some(Secret(contents.trim_end_matches(['\r', '\n']).to_string())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars = vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<HashMap<String, String>>();
+        move |name| vars.get(name).cloned()
+    }
+
+    fn auth_from(yaml: &str) -> AuthSettings {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn defaults_to_plain_http() {
+        let conn = Connection::resolve(None, AuthSettings::default(), TlsSettings::default(), &env(&[])).unwrap();
+        assert_eq!(conn, Connection::default());
+        assert_eq!(conn.scheme.to_string(), "http");
+    }
+
+    #[test]
+    fn config_wins_over_environment() {
+        let conn = Connection::resolve(
+            Some("https".to_string()),
+            auth_from("username: elastic\npassword: changeme\n"),
+            TlsSettings::default(),
+            &env(&[(SCHEMEVAR, "http"), (USERNAME_VAR, "other"), (PASSWORD_VAR, "other")]),
+        ).unwrap();
+        assert_eq!(conn.scheme, Scheme::Https);
+        assert_eq!(conn.credentials, Credentials::Basic {
+            username: "elastic".to_string(),
+            password: Some("changeme".into()),
+        });
+    }
+
+    #[test]
+    fn reads_secrets_from_files() {
+        let path = std::env::temp_dir().join(format!("elasticlean-secret-{}", std::process::id()));
+        fs::write(&path, "c2VjcmV0\n").unwrap();
+
+        let conn = Connection::resolve(None, AuthSettings::default(), TlsSettings::default(),
+            &env(&[("ELASTICLEAN_API_KEY_FILE", path.to_str().unwrap())])).unwrap();
+        assert_eq!(conn.credentials, Credentials::ApiKey("c2VjcmV0".into()));
+
+        let yaml = format!("bearer_token_file: {}\n", path.display());
+        let conn = Connection::resolve(None, auth_from(&yaml), TlsSettings::default(), &env(&[])).unwrap();
+        assert_eq!(conn.credentials, Credentials::Bearer("c2VjcmV0".into()));
+
+        fs::remove_file(&path).unwrap();
+        assert!(Connection::resolve(None, auth_from(&yaml), TlsSettings::default(), &env(&[])).is_err());
+    }
+
+    #[test]
+    fn conflicting_credentials_produce_error() {
+        let auth = auth_from("username: elastic\napi_key: abc\n");
+        assert!(Connection::resolve(None, auth, TlsSettings::default(), &env(&[])).is_err());
+        let auth = auth_from("password: changeme\n");
+        assert!(Connection::resolve(None, auth, TlsSettings::default(), &env(&[])).is_err());
+    }
+
+    #[test]
+    fn certificates_imply_https() {
+        let conn = Connection::resolve(None, AuthSettings::default(), TlsSettings::default(),
+            &env(&[(CA_CERT_VAR, "/etc/ssl/ca.pem")])).unwrap();
+        assert_eq!(conn.scheme, Scheme::Https);
+        assert_eq!(conn.ca_cert, Some(PathBuf::from("/etc/ssl/ca.pem")));
+        assert!(conn.client().is_err());
+    }
+
+    #[test]
+    fn secrets_stay_out_of_debug_output() {
+        let auth = auth_from("username: elastic\npassword: changeme\n");
+        let conn = Connection::resolve(None, auth, TlsSettings::default(), &env(&[])).unwrap();
+        assert!(!format!("{:?}", conn).contains("changeme"));
+    }
+}
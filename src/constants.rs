@@ -6,10 +6,77 @@
 /// The minimum number of days if not specified
 pub const MIN_DAYS: i32 = 60;
 
+/// The default node disk usage percentage above which the watermark
+/// command starts deleting indices
+pub const HIGH_WATERMARK: u32 = 85;
+
+/// The default node disk usage percentage the watermark command
+/// deletes indices down to
+pub const LOW_WATERMARK: u32 = 75;
+
+/// The number of seconds to wait between polls of a snapshot's status
+pub const SNAPSHOT_POLL_SECS: u64 = 5;
+
+/// The number of seconds to wait for a snapshot to complete before giving up
+pub const SNAPSHOT_TIMEOUT_SECS: u64 = 3600;
+
+/// The number of seconds to wait between polls of a reindex task
+pub const REINDEX_POLL_SECS: u64 = 5;
+
+/// The number of seconds to wait for a reindex to complete before giving up
+pub const REINDEX_TIMEOUT_SECS: u64 = 4 * 3600;
+
 /// The environment variable name to specify the elasticsearch host.
 pub(crate) const HOSTVAR: &'static str = "ELASTICLEAN_HOST";
 
 /// The environment variable name to specify the elasticsearch port.
 pub(crate) const PORTVAR: &'static str = "ELASTICLEAN_PORT";
 
-pub(crate) const MIN_DAYS_VAR: &'static str = "ELASTICLEAN_MIN_DAYS";
\ No newline at end of file
+/// The environment variable name to specify the full base url of
+/// elasticsearch, including any path prefix (eg https://gateway/es-prod/),
+/// in place of the host and port. Several urls, tried in order, may be
+/// separated by commas.
+pub(crate) const URLVAR: &str = "ELASTICLEAN_URL";
+
+/// The environment variable name to specify whether to ask the cluster for
+/// the http addresses of its nodes (true or false).
+pub(crate) const SNIFFVAR: &str = "ELASTICLEAN_SNIFF";
+
+pub(crate) const MIN_DAYS_VAR: &'static str = "ELASTICLEAN_MIN_DAYS";
+
+/// The environment variable name to specify the timezone index dates are
+/// reckoned in (eg UTC, local, or +05:30).
+pub(crate) const TIMEZONE_VAR: &str = "ELASTICLEAN_TIMEZONE";
+
+/// The environment variable name to specify the number of hours after the
+/// end of its period before an index starts to age.
+pub(crate) const GRACE_HOURS_VAR: &str = "ELASTICLEAN_GRACE_HOURS";
+
+/// The environment variable name to specify the path to the yaml config file.
+pub(crate) const CONFIGVAR: &str = "ELASTICLEAN_CONFIG";
+
+/// The environment variable name to specify the path to the state file
+/// holding the indices protected by the protect command.
+pub(crate) const STATEVAR: &str = "ELASTICLEAN_STATE";
+
+/// The name of the state file, in the home directory, used if neither the
+/// config file nor the environment supply one.
+pub(crate) const STATE_FILE: &str = ".elasticlean_holds.json";
+
+/// The environment variable name to specify the scheme, http or https,
+/// used to reach elasticsearch.
+pub(crate) const SCHEMEVAR: &str = "ELASTICLEAN_SCHEME";
+
+/// The environment variable names to specify the credentials sent with
+/// every request. Each may instead be read from the file named by the same
+/// variable with a `_FILE` suffix (eg `ELASTICLEAN_PASSWORD_FILE`).
+pub(crate) const USERNAME_VAR: &str = "ELASTICLEAN_USERNAME";
+pub(crate) const PASSWORD_VAR: &str = "ELASTICLEAN_PASSWORD";
+pub(crate) const API_KEY_VAR: &str = "ELASTICLEAN_API_KEY";
+pub(crate) const BEARER_TOKEN_VAR: &str = "ELASTICLEAN_BEARER_TOKEN";
+
+/// The environment variable names to specify a PEM CA bundle to trust, and
+/// a PKCS #12 client certificate, along with its password, to present.
+pub(crate) const CA_CERT_VAR: &str = "ELASTICLEAN_CA_CERT";
+pub(crate) const CLIENT_CERT_VAR: &str = "ELASTICLEAN_CLIENT_CERT";
+pub(crate) const CLIENT_CERT_PASSWORD_VAR: &str = "ELASTICLEAN_CLIENT_CERT_PASSWORD";
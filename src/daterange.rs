@@ -0,0 +1,252 @@
+//! # daterange.rs
+//!
+//! Selection of indices by date. A DateRange is built up from bounds on the
+//! period an index covers, each expressed either as an ISO date
+//! (eg `2018-03-01`) or relative to today (eg `90d`, `6w`). Relative bounds
+//! are resolved when the range is matched, so a range may be built before
+//! the date it is resolved against is known.
+use chrono::{naive::NaiveDate, Duration};
+use crate::errors::EcError;
+use crate::index::Index;
+use std::{fmt, str::FromStr};
+
+/// A date, either absolute or a number of days before today
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateExpr {
+    /// An ISO date, eg `2018-03-01`
+    Date(NaiveDate),
+    /// A number of days before today, eg `90d`, or `6w` for 42 days
+    DaysAgo(i64),
+}
+
+impl DateExpr {
+    /// Resolve the expression into a date
+    ///
+    /// # Parameters
+    ///
+    /// * `today` - The date relative expressions are measured back from
+    ///
+    /// # Returns
+    ///
+    /// * `NaiveDate` instance
+    pub fn resolve(&self, today: NaiveDate) -> NaiveDate {
+        match *self {
+            DateExpr::Date(date) => date,
+            DateExpr::DaysAgo(days) => today - Duration::days(days),
+        }
+    }
+}
+
+impl FromStr for DateExpr {
+    type Err = EcError;
+
+    fn from_str(input: &str) -> Result<DateExpr, EcError> {
+        let err = || EcError::ParseError(
+            format!("date {}: expected YYYY-MM-DD, or a number of days (90d) or weeks (6w) ago", input));
+        let input = input.trim();
+
+        if let Ok(date) = NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+            return Ok(DateExpr::Date(date));
+        }
+        let (count, unit) = input.split_at(input.len().saturating_sub(1));
+        let count = count.parse::<i64>().map_err(|_| err())?;
+        match unit {
+            "d" | "D" => Ok(DateExpr::DaysAgo(count)),
+            "w" | "W" => Ok(DateExpr::DaysAgo(count * 7)),
+            _ => Err(err()),
+        }
+    }
+}
+
+impl fmt::Display for DateExpr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DateExpr::Date(date) => write!(f, "{}", date.format("%Y-%m-%d")),
+            DateExpr::DaysAgo(days) => write!(f, "{}d", days),
+        }
+    }
+}
+
+// A single constraint on the period covered by an index
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Bound {
+    // the period ends before the date
+    Before(DateExpr),
+    // the period starts after the date
+    After(DateExpr),
+    // the period starts on or after the date
+    From(DateExpr),
+    // the period ends on or before the date
+    To(DateExpr),
+    // the period ends on or after the date
+    EndsFrom(DateExpr),
+}
+
+/// A filter on the period covered by an index. Every bound added to the
+/// range must hold for an index to match; a range without bounds matches
+/// every index.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DateRange {
+    bounds: Vec<Bound>,
+}
+
+impl DateRange {
+    /// New up a range which matches every index
+    pub fn all() -> DateRange {
+        DateRange::default()
+    }
+
+    /// New up a range from the cli's day offsets. The range holds indices
+    /// no more than `start` days old and more than `end` days old, where
+    /// age is measured from the last day an index covers.
+    ///
+    /// # Parameters
+    ///
+    /// * `start` - Optional start offset, in days, from today
+    /// * `end`   - Optional end offset, in days, from today
+    ///
+    /// # Returns
+    ///
+    /// * `DateRange` instance
+    pub fn from_offsets(start: Option<i32>, end: Option<i32>) -> DateRange {
+        let mut range = DateRange::all();
+        if let Some(start) = start {
+            range.bounds.push(Bound::EndsFrom(DateExpr::DaysAgo(start.into())));
+        }
+        if let Some(end) = end {
+            range.bounds.push(Bound::Before(DateExpr::DaysAgo(end.into())));
+        }
+        range
+    }
+
+    /// Restrict the range to indices which end before a date
+    pub fn before(mut self, date: DateExpr) -> DateRange {
+        self.bounds.push(Bound::Before(date));
+        self
+    }
+
+    /// Restrict the range to indices which start after a date
+    pub fn after(mut self, date: DateExpr) -> DateRange {
+        self.bounds.push(Bound::After(date));
+        self
+    }
+
+    /// Restrict the range to indices which lie within a single day
+    pub fn on(self, date: DateExpr) -> DateRange {
+        self.between(date, date)
+    }
+
+    /// Restrict the range to indices which lie within two dates, inclusive
+    pub fn between(mut self, first: DateExpr, last: DateExpr) -> DateRange {
+        self.bounds.push(Bound::From(first));
+        self.bounds.push(Bound::To(last));
+        self
+    }
+
+    /// Restrict the range to indices which also lie within another range
+    pub fn and(mut self, other: DateRange) -> DateRange {
+        self.bounds.extend(other.bounds);
+        self
+    }
+
+    /// Whether the range matches every index
+    pub fn is_unbounded(&self) -> bool {
+        self.bounds.is_empty()
+    }
+
+    /// Whether an index lies within the range
+    ///
+    /// # Parameters
+    ///
+    /// * `idx`   - The index
+    /// * `today` - The date relative bounds are measured back from
+    ///
+    /// # Returns
+    ///
+    /// * `bool`
+    pub fn matches(&self, idx: &Index, today: NaiveDate) -> bool {
+        let first = idx.date;
+        let last = idx.last_date();
+        self.bounds.iter().all(|bound| match bound {
+            Bound::Before(d) => last < d.resolve(today),
+            Bound::After(d) => first > d.resolve(today),
+            Bound::From(d) => first >= d.resolve(today),
+            Bound::To(d) => last <= d.resolve(today),
+            Bound::EndsFrom(d) => last >= d.resolve(today),
+        })
+    }
+}
+
+/// Parses `A..B` into a range holding the indices between A and B inclusive
+impl FromStr for DateRange {
+    type Err = EcError;
+
+    fn from_str(input: &str) -> Result<DateRange, EcError> {
+        let mut pieces = input.splitn(2, "..");
+        match (pieces.next(), pieces.next()) {
+            (Some(first), Some(last)) => Ok(DateRange::all().between(first.parse()?, last.parse()?)),
+            _ => Err(EcError::ParseError(format!("date range {}: expected A..B", input))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn can_parse_dates_and_relative_expressions() {
+        assert_eq!("2018-03-01".parse::<DateExpr>().unwrap(), DateExpr::Date(date(2018, 3, 1)));
+        assert_eq!("90d".parse::<DateExpr>().unwrap(), DateExpr::DaysAgo(90));
+        assert_eq!("6w".parse::<DateExpr>().unwrap(), DateExpr::DaysAgo(42));
+        assert!("2018-02-30".parse::<DateExpr>().is_err());
+        assert!("6m".parse::<DateExpr>().is_err());
+        assert!("d".parse::<DateExpr>().is_err());
+    }
+
+    #[test]
+    fn relative_expressions_resolve_against_today() {
+        assert_eq!(DateExpr::DaysAgo(42).resolve(date(2018, 10, 12)), date(2018, 8, 31));
+    }
+
+    #[test]
+    fn between_holds_whole_periods() {
+        let range = "2018-03-01..2018-03-31".parse::<DateRange>().unwrap();
+        let today = date(2018, 10, 12);
+        assert!(range.matches(&Index::new("foo", 2018, 3, 1), today));
+        assert!(range.matches(&Index::new("foo", 2018, 3, 31), today));
+        assert!(range.matches(&Index::new_monthly("foo", 2018, 3), today));
+        assert!(!range.matches(&Index::new("foo", 2018, 4, 1), today));
+        assert!(!range.matches(&Index::new_monthly("foo", 2018, 4), today));
+    }
+
+    #[test]
+    fn before_after_and_on() {
+        let today = date(2018, 10, 12);
+        let before = DateRange::all().before(DateExpr::Date(date(2018, 3, 1)));
+        assert!(before.matches(&Index::new("foo", 2018, 2, 28), today));
+        assert!(!before.matches(&Index::new("foo", 2018, 3, 1), today));
+
+        let after = DateRange::all().after(DateExpr::DaysAgo(2));
+        assert!(after.matches(&Index::new("foo", 2018, 10, 11), today));
+        assert!(!after.matches(&Index::new("foo", 2018, 10, 10), today));
+
+        let on = DateRange::all().on(DateExpr::Date(date(2018, 3, 1)));
+        assert!(on.matches(&Index::new("foo", 2018, 3, 1), today));
+        assert!(!on.matches(&Index::new("foo", 2018, 3, 2), today));
+    }
+
+    #[test]
+    fn offsets_match_index_age() {
+        let today = date(2018, 10, 12);
+        let range = DateRange::from_offsets(Some(10), Some(5));
+        assert!(range.matches(&Index::new("foo", 2018, 10, 2), today));
+        assert!(range.matches(&Index::new("foo", 2018, 10, 6), today));
+        assert!(!range.matches(&Index::new("foo", 2018, 10, 1), today));
+        assert!(!range.matches(&Index::new("foo", 2018, 10, 7), today));
+    }
+}
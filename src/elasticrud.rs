@@ -6,12 +6,21 @@
 //! are not exposed as public api.
 //!
 use crate::{
+    connection::Connection,
     errors::EcError,
     index::Index,
+    rawalias::RawAlias,
+    rawallocation::RawAllocation,
+    rawdatastream::{RawDataStream, RawDataStreamRoot},
     rawindex::RawIndex,
+    rawnodes::RawNodesRoot,
+    rawshard::{merged_indices, write_blocked, RawIndexSettings, RawShard},
+    rawsnapshot::{RawSnapshot, RawSnapshotRoot, RawSnapshotStatus, RawSnapshotStatusRoot},
+    rawtask::{RawTask, RawTaskId},
     traits::ElasticIndex,
 };
-use reqwest;
+use reqwest::{self, Client, Method, Response, StatusCode, Url};
+use std::{cell::Cell, collections::{BTreeMap, HashSet}, fmt};
 
 /// The outer map returned by elasticsearch _search results
 #[derive(Deserialize, Debug)]
@@ -42,32 +51,116 @@ pub(crate) struct EsSearchMeta<I> {
     pub source: I,
 }
 
+/// A request's path and query, which may be sent to any node
+#[derive(Debug, PartialEq, Eq)]
+struct Route {
+    segments: Vec<String>,
+    query: Vec<(String, String)>,
+}
+
+impl Route {
+    // The full url of the route on a node. Each segment and parameter is
+    // percent-encoded, so an index name may hold characters such as `#` or
+    // `?`; the commas separating several indices in one segment are left alone.
+    fn on(&self, base: &Url) -> Result<Url, EcError> {
+        let mut url = base.clone();
+        url.path_segments_mut()
+            .map_err(|_| EcError::InvalidArgument(format!("{} cannot be a base url", base)))?
+            .pop_if_empty()
+            .extend(&self.segments);
+        if !self.query.is_empty() {
+            url.query_pairs_mut().extend_pairs(&self.query);
+        }
+        Ok(url)
+    }
+}
+
+impl fmt::Display for Route {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "/{}", self.segments.join("/"))?;
+        for (i, (key, value)) in self.query.iter().enumerate() {
+            write!(f, "{}{}={}", if i == 0 { "?" } else { "&" }, key, value)?;
+        }
+        Ok(())
+    }
+}
+
 /// Responsible for providing basic crud over indices
 #[derive(Debug)]
 pub struct Elasticrud<'a> {
-    host: &'a str,
-    port: u16,
+    nodes: Vec<Url>,
+    preferred: Cell<usize>,
+    connection: &'a Connection,
+    client: Client,
 }
 
 
 impl<'a> Elasticrud<'a> {
-    /// New up an instance of Elasticrud given a host and port number
+    /// New up an instance of Elasticrud given the base urls of the nodes of
+    /// elasticsearch, and the credentials and certificates to connect with.
+    /// Each request goes to the node which last answered, failing over to
+    /// the others in order when it cannot be reached or, for a GET, answers
+    /// 502, 503 or 504. Nodes are not otherwise checked for health.
     ///
     /// # Parameters
     ///
-    /// * `host` - The host url
-    /// * `port` - The port number
+    /// * `nodes` - The base urls, eg `http://localhost:9200/` or, behind a
+    ///   proxy, `https://gateway/es-prod/`
+    /// * `connection` - How to authenticate to the nodes
+    /// * `sniff` - Whether to ask the cluster for the http addresses of its
+    ///   nodes, which are tried after those given. If the cluster cannot be
+    ///   asked, the given nodes are used alone.
     ///
     /// # Returns
     ///
-    /// * `Elasticrud` instance
-    pub fn new(host: &'a str, port: u16) -> Elasticrud<'a>
+    /// * `Elasticrud` instance if successful
+    /// * `EcError` if no nodes are given, or the connection's certificates
+    ///   cannot be loaded
+    pub fn new(nodes: &[Url], connection: &'a Connection, sniff: bool) -> Result<Elasticrud<'a>, EcError>
 
     {
-        Elasticrud {
-            host: host,//.into(),
-            port,
+        if nodes.is_empty() {
+            return Err(EcError::InvalidArgument("no elasticsearch urls given".to_string()));
+        }
+        let mut ec = Elasticrud {
+            nodes: nodes.to_vec(),
+            preferred: Cell::new(0),
+            connection,
+            client: connection.client()?,
+        };
+        if sniff {
+            match ec.sniff() {
+                Ok(nodes) => ec.add_nodes(nodes),
+                Err(e) => warn!("unable to sniff nodes, using those configured: {}", e),
+            }
         }
+        Ok(ec)
+    }
+
+    /// Retrieve the base urls of the nodes in the cluster which serve http,
+    /// using the scheme of the first configured node
+    ///
+    /// # Parameters
+    ///
+    /// None
+    ///
+    /// # Returns
+    ///
+    /// * `Vector` of `Url`s if successful
+    /// * `EcError` if unsuccesful
+    pub fn sniff(&self) -> Result<Vec<Url>, EcError> {
+        let route = self.get_route(&["_nodes", "http"], &[]);
+        debug!("Elasticrud.sniff - route {}", route);
+
+        let body: RawNodesRoot = self.send(Method::GET, &route, None)?
+                                .json()
+                                .map_err(|e| EcError::ReqwestJsonError(format!("{}",e)))?;
+        let scheme = self.nodes[0].scheme();
+        body.nodes.values()
+            .filter_map(|node| node.http_address())
+            .map(|address| Url::parse(&format!("{}://{}/", scheme, address))
+                 .map_err(|e| EcError::InvalidArgument(format!("node address {}: {}", address, e))))
+            .collect()
     }
 
     /// Retrieve a list of indices from elasticsearch
@@ -81,11 +174,74 @@ impl<'a> Elasticrud<'a> {
     /// * `Vector` of `RawIndex` instances if successful
     /// * `EcError` if unsuccesful
     pub fn get_raw_indices(&self) -> Result<Vec<RawIndex>, EcError> {
-        let route = self.get_route("_cat/indices?format=json");
+        let route = self.get_route(&["_cat", "indices"], &[
+            ("format", "json"),
+            ("h", "health,status,index,pri,rep,docs.count,store.size,pri.store.size,creation.date"),
+        ]);
         debug!("Elasticrud.get - route {}", route);
 
-        let body: Vec<RawIndex> = reqwest::get(&route)
-                                .map_err(|e| EcError::ReqwestGetError(format!("{}",e)))?
+        let body: Vec<RawIndex> = self.send(Method::GET, &route, None)?
+                                .json()
+                                .map_err(|e| EcError::ReqwestJsonError(format!("{}",e)))?;
+        Ok(body)
+    }
+
+    /// Retrieve the aliases defined in the cluster, one row per alias and index
+    ///
+    /// # Parameters
+    ///
+    /// None
+    ///
+    /// # Returns
+    ///
+    /// * `Vector` of `RawAlias` instances if successful
+    /// * `EcError` if unsuccesful
+    pub fn get_aliases(&self) -> Result<Vec<RawAlias>, EcError> {
+        let route = self.get_route(&["_cat", "aliases"], &[("format", "json")]);
+        debug!("Elasticrud.get_aliases - route {}", route);
+
+        let body: Vec<RawAlias> = self.send(Method::GET, &route, None)?
+                                .json()
+                                .map_err(|e| EcError::ReqwestJsonError(format!("{}",e)))?;
+        Ok(body)
+    }
+
+    /// Retrieve the data streams defined in the cluster, along with their
+    /// backing indices
+    ///
+    /// # Parameters
+    ///
+    /// None
+    ///
+    /// # Returns
+    ///
+    /// * `Vector` of `RawDataStream` instances if successful
+    /// * `EcError` if unsuccesful
+    pub fn get_data_streams(&self) -> Result<Vec<RawDataStream>, EcError> {
+        let route = self.get_route(&["_data_stream"], &[]);
+        debug!("Elasticrud.get_data_streams - route {}", route);
+
+        let body: RawDataStreamRoot = self.send(Method::GET, &route, None)?
+                                .json()
+                                .map_err(|e| EcError::ReqwestJsonError(format!("{}",e)))?;
+        Ok(body.data_streams)
+    }
+
+    /// Retrieve the disk usage of each node in the cluster, in bytes
+    ///
+    /// # Parameters
+    ///
+    /// None
+    ///
+    /// # Returns
+    ///
+    /// * `Vector` of `RawAllocation` instances if successful
+    /// * `EcError` if unsuccesful
+    pub fn get_allocation(&self) -> Result<Vec<RawAllocation>, EcError> {
+        let route = self.get_route(&["_cat", "allocation"], &[("format", "json"), ("bytes", "b")]);
+        debug!("Elasticrud.get_allocation - route {}", route);
+
+        let body: Vec<RawAllocation> = self.send(Method::GET, &route, None)?
                                 .json()
                                 .map_err(|e| EcError::ReqwestJsonError(format!("{}",e)))?;
         Ok(body)
@@ -99,7 +255,7 @@ impl<'a> Elasticrud<'a> {
     ///
     /// # Parameters
     ///
-    /// * `indices` - References to a `Vector` of `Index`instances
+    /// * `indices` - Slice of `Index`instances
     ///
     /// # Returns
     ///
@@ -112,22 +268,18 @@ impl<'a> Elasticrud<'a> {
     /// let indices = vec![Index::from_str("foobar-2018.10.02")?];
     /// let results = ec.get::<MyIndexData>(indices)?;
     /// ```
-    pub fn get<I>(&self, indices: &Vec<Index>) -> Result<Vec<I>, EcError>
+    pub fn get<I>(&self, indices: &[Index]) -> Result<Vec<I>, EcError>
     where
         //for<'de> I: serde::Deserialize<'de>
         I: ElasticIndex
     {
         // build a comma separated string of indexes
-        let indices = indices.into_iter()
-            .map(|i| format!("{}",i))
-            .collect::<Vec<String>>()
-            .join(",");
+        let indices = join_indices(indices);
 
         // build a search route
-        let route = self.get_route(format!("{}/_search", indices).as_str());
+        let route = self.get_route(&[&indices, "_search"], &[]);
         info!("get_indices route {}", route);
-        let body: EsSearchRoot<I> = reqwest::get(&route)
-                                .map_err(|e| EcError::ReqwestGetError(format!("{}",e)))?
+        let body: EsSearchRoot<I> = self.send(Method::GET, &route, None)?
                                 .json()
                                 .map_err(|e| EcError::ReqwestJsonError(format!("{}",e)))?;
 
@@ -141,33 +293,539 @@ impl<'a> Elasticrud<'a> {
     ///
     /// # Parameters
     ///
-    /// * `indices` - Reference to a `Vector` Of Index instances
+    /// * `indices` - Slice of Index instances
     ///
     /// # Returns
     ///
     /// * `request::Response` instance if successful
     /// * `EcError` instance if unsuccessful
-    pub fn delete_indices(&self, indices: &Vec<Index>) -> Result<reqwest::Response, EcError> {
+    pub fn delete_indices(&self, indices: &[Index]) -> Result<reqwest::Response, EcError> {
         // generate a String
-        let idxs = indices.into_iter()
-        .map(|i| format!("{}",i))
-        .collect::<Vec<String>>()
-        .join(",");
+        let idxs = join_indices(indices);
         // get route from idxs
-        let route = self.get_route(idxs.as_str());
+        let route = self.get_route(&[&idxs], &[]);
         debug!("Elasticrud.delete_indices - route {}", route);
 
-        let client = reqwest::Client::new();
-        client.delete(&route)
-            .send()
+        self.send(Method::DELETE, &route, None)?
+            .error_for_status()
+            .map_err(|e| EcError::ReqwestGetError(format!("{}",e)))
+    }
+
+    /// Start a snapshot of one or more indices in a snapshot repository.
+    /// This returns once elasticsearch accepts the request; use
+    /// `get_snapshot_status` to follow its progress.
+    ///
+    /// # Parameters
+    ///
+    /// * `repo`     - The name of the snapshot repository
+    /// * `snapshot` - The name of the snapshot to create
+    /// * `indices`  - Slice of Index instances to include in the snapshot
+    ///
+    /// # Returns
+    ///
+    /// * `()` if successful
+    /// * `EcError` instance if unsuccessful
+    pub fn create_snapshot(&self, repo: &str, snapshot: &str, indices: &[Index]) -> Result<(), EcError> {
+        let idxs = join_indices(indices);
+        let route = self.get_route(&["_snapshot", repo, snapshot], &[]);
+        debug!("Elasticrud.create_snapshot - route {}", route);
+
+        let body = json!({
+            "indices": idxs,
+            "ignore_unavailable": false,
+            "include_global_state": false,
+        });
+
+        self.send(Method::PUT, &route, Some(&body))?
+            .error_for_status()
+            .map_err(|e| EcError::SnapshotError(format!("{}",e)))?;
+        Ok(())
+    }
+
+    /// Retrieve the status of a snapshot, including the state of each
+    /// index within it.
+    ///
+    /// # Parameters
+    ///
+    /// * `repo`     - The name of the snapshot repository
+    /// * `snapshot` - The name of the snapshot
+    ///
+    /// # Returns
+    ///
+    /// * `RawSnapshotStatus` instance if successful
+    /// * `EcError` instance if unsuccessful
+    pub fn get_snapshot_status(&self, repo: &str, snapshot: &str) -> Result<RawSnapshotStatus, EcError> {
+        let route = self.get_route(&["_snapshot", repo, snapshot, "_status"], &[]);
+        debug!("Elasticrud.get_snapshot_status - route {}", route);
+
+        let body: RawSnapshotStatusRoot = self.send(Method::GET, &route, None)?
+                                .json()
+                                .map_err(|e| EcError::ReqwestJsonError(format!("{}",e)))?;
+        body.snapshots.into_iter()
+            .next()
+            .ok_or_else(|| EcError::SnapshotError(format!("no status for snapshot {}/{}", repo, snapshot)))
+    }
+
+    /// Retrieve the snapshots in a snapshot repository
+    ///
+    /// # Parameters
+    ///
+    /// * `repo` - The name of the snapshot repository
+    ///
+    /// # Returns
+    ///
+    /// * `Vector` of `RawSnapshot` instances if successful
+    /// * `EcError` instance if unsuccessful
+    pub fn get_snapshots(&self, repo: &str) -> Result<Vec<RawSnapshot>, EcError> {
+        let route = self.get_route(&["_snapshot", repo, "_all"], &[]);
+        debug!("Elasticrud.get_snapshots - route {}", route);
+
+        let body: RawSnapshotRoot = self.send(Method::GET, &route, None)?
+                                .json()
+                                .map_err(|e| EcError::ReqwestJsonError(format!("{}",e)))?;
+        Ok(body.snapshots)
+    }
+
+    /// Restore indices from a snapshot. This returns once elasticsearch
+    /// accepts the request; the restore itself continues in the background.
+    ///
+    /// # Parameters
+    ///
+    /// * `repo`          - The name of the snapshot repository
+    /// * `snapshot`      - The name of the snapshot to restore from
+    /// * `indices`       - Names of the indices to restore
+    /// * `rename_prefix` - Optional prefix added to the name of each restored index
+    ///
+    /// # Returns
+    ///
+    /// * `request::Response` instance if successful
+    /// * `EcError` instance if unsuccessful
+    pub fn restore_snapshot(&self, repo: &str, snapshot: &str, indices: &[String], rename_prefix: Option<&str>)
+    -> Result<reqwest::Response, EcError> {
+        let route = self.get_route(&["_snapshot", repo, snapshot, "_restore"], &[]);
+        debug!("Elasticrud.restore_snapshot - route {}", route);
+
+        let mut body = json!({
+            "indices": indices.join(","),
+            "include_global_state": false,
+        });
+        if let Some(prefix) = rename_prefix {
+            body["rename_pattern"] = json!("(.+)");
+            body["rename_replacement"] = json!(format!("{}$1", prefix));
+        }
+
+        self.send(Method::POST, &route, Some(&body))?
+            .error_for_status()
+            .map_err(|e| EcError::ReqwestGetError(format!("{}",e)))
+    }
+
+    /// Close one or more indices. Closed indices hold on to their data on disk,
+    /// but can not be searched until they are reopened.
+    ///
+    /// # Parameters
+    ///
+    /// * `indices` - Slice of Index instances to close
+    ///
+    /// # Returns
+    ///
+    /// * `request::Response` instance if successful
+    /// * `EcError` instance if unsuccessful
+    pub fn close_indices(&self, indices: &[Index]) -> Result<reqwest::Response, EcError> {
+        let route = self.get_route(&[&join_indices(indices), "_close"], &[]);
+        debug!("Elasticrud.close_indices - route {}", route);
+
+        self.send(Method::POST, &route, None)?
+            .error_for_status()
+            .map_err(|e| EcError::ReqwestGetError(format!("{}",e)))
+    }
+
+    /// Force merge one or more indices, reducing the number of segments
+    /// in each shard.
+    ///
+    /// # Parameters
+    ///
+    /// * `indices`          - Slice of Index instances to force merge
+    /// * `max_num_segments` - The number of segments to merge down to
+    ///
+    /// # Returns
+    ///
+    /// * `request::Response` instance if successful
+    /// * `EcError` instance if unsuccessful
+    pub fn forcemerge_indices(&self, indices: &[Index], max_num_segments: u32) -> Result<reqwest::Response, EcError> {
+        let route = self.get_route(&[&join_indices(indices), "_forcemerge"],
+                                   &[("max_num_segments", &max_num_segments.to_string())]);
+        debug!("Elasticrud.forcemerge_indices - route {}", route);
+
+        self.send(Method::POST, &route, None)?
+            .error_for_status()
+            .map_err(|e| EcError::ReqwestGetError(format!("{}",e)))
+    }
+
+    /// Retrieve the names of those of one or more indices whose shards all
+    /// hold no more than `max_num_segments` segments
+    ///
+    /// # Parameters
+    ///
+    /// * `indices`          - Slice of Index instances to check
+    /// * `max_num_segments` - The number of segments a force merge would merge down to
+    ///
+    /// # Returns
+    ///
+    /// * `HashSet` of index names if successful
+    /// * `EcError` instance if unsuccessful
+    pub fn get_merged(&self, indices: &[Index], max_num_segments: u32) -> Result<HashSet<String>, EcError> {
+        let route = self.get_route(&["_cat", "shards", &join_indices(indices)], &[
+            ("format", "json"),
+            ("h", "index,shard,prirep,segments.count"),
+        ]);
+        debug!("Elasticrud.get_merged - route {}", route);
+
+        let shards: Vec<RawShard> = self.send(Method::GET, &route, None)?
+                                .json()
+                                .map_err(|e| EcError::ReqwestJsonError(format!("{}",e)))?;
+        Ok(merged_indices(&shards, max_num_segments))
+    }
+
+    /// Retrieve the names of those of one or more indices which already
+    /// block writes
+    ///
+    /// # Parameters
+    ///
+    /// * `indices` - Slice of Index instances to check
+    ///
+    /// # Returns
+    ///
+    /// * `HashSet` of index names if successful
+    /// * `EcError` instance if unsuccessful
+    pub fn get_write_blocked(&self, indices: &[Index]) -> Result<HashSet<String>, EcError> {
+        let route = self.get_route(&[&join_indices(indices), "_settings", "index.blocks.write"],
+                                   &[("flat_settings", "true")]);
+        debug!("Elasticrud.get_write_blocked - route {}", route);
+
+        let settings: BTreeMap<String, RawIndexSettings> = self.send(Method::GET, &route, None)?
+                                .json()
+                                .map_err(|e| EcError::ReqwestJsonError(format!("{}",e)))?;
+        Ok(write_blocked(&settings))
+    }
+
+    /// Update the settings of one or more indices
+    ///
+    /// # Parameters
+    ///
+    /// * `indices`  - Slice of Index instances to update
+    /// * `settings` - The settings to apply (eg `{"index.blocks.write": true}`)
+    ///
+    /// # Returns
+    ///
+    /// * `request::Response` instance if successful
+    /// * `EcError` instance if unsuccessful
+    pub fn update_settings(&self, indices: &[Index], settings: &serde_json::Value) -> Result<reqwest::Response, EcError> {
+        let route = self.get_route(&[&join_indices(indices), "_settings"], &[]);
+        debug!("Elasticrud.update_settings - route {}", route);
+
+        self.send(Method::PUT, &route, Some(settings))?
+            .error_for_status()
+            .map_err(|e| EcError::ReqwestGetError(format!("{}",e)))
+    }
+
+    /// Start copying the documents of one or more indices into another
+    /// index. The reindex runs as a task in the background; use `get_task`
+    /// to follow its progress.
+    ///
+    /// # Parameters
+    ///
+    /// * `indices` - Slice of Index instances to copy from
+    /// * `dest`    - The index to copy into
+    ///
+    /// # Returns
+    ///
+    /// * The id of the reindex task if successful
+    /// * `EcError` instance if unsuccessful
+    pub fn reindex(&self, indices: &[Index], dest: &Index) -> Result<String, EcError> {
+        let route = self.get_route(&["_reindex"], &[("wait_for_completion", "false")]);
+        debug!("Elasticrud.reindex - route {}", route);
+
+        let sources = indices.iter()
+            .map(|i| format!("{}",i))
+            .collect::<Vec<String>>();
+        let body = json!({
+            "source": { "index": sources },
+            "dest": { "index": format!("{}", dest) },
+        });
+
+        let task: RawTaskId = self.send(Method::POST, &route, Some(&body))?
+            .error_for_status()
+            .map_err(|e| EcError::ReqwestGetError(format!("{}",e)))?
+            .json()
+            .map_err(|e| EcError::ReqwestJsonError(format!("{}",e)))?;
+        Ok(task.task)
+    }
+
+    /// Retrieve the state of a task
+    ///
+    /// # Parameters
+    ///
+    /// * `task` - The id of the task
+    ///
+    /// # Returns
+    ///
+    /// * `RawTask` instance if successful
+    /// * `EcError` instance if unsuccessful
+    pub fn get_task(&self, task: &str) -> Result<RawTask, EcError> {
+        let route = self.get_route(&["_tasks", task], &[]);
+        debug!("Elasticrud.get_task - route {}", route);
+
+        let body: RawTask = self.send(Method::GET, &route, None)?
+                                .json()
+                                .map_err(|e| EcError::ReqwestJsonError(format!("{}",e)))?;
+        Ok(body)
+    }
+
+    /// Refresh one or more indices, making recently written documents
+    /// visible to searches and counts
+    ///
+    /// # Parameters
+    ///
+    /// * `indices` - Slice of Index instances to refresh
+    ///
+    /// # Returns
+    ///
+    /// * `request::Response` instance if successful
+    /// * `EcError` instance if unsuccessful
+    pub fn refresh_indices(&self, indices: &[Index]) -> Result<reqwest::Response, EcError> {
+        let route = self.get_route(&[&join_indices(indices), "_refresh"], &[]);
+        debug!("Elasticrud.refresh_indices - route {}", route);
+
+        self.send(Method::POST, &route, None)?
+            .error_for_status()
             .map_err(|e| EcError::ReqwestGetError(format!("{}",e)))
+    }
+
+    // Build a route from path segments and query parameters
+    fn get_route(&self, segments: &[&str], query: &[(&str, &str)]) -> Route {
+        Route {
+            segments: segments.iter().map(|s| s.to_string()).collect(),
+            query: query.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+        }
+    }
+
+    // Add nodes to try after those already known, skipping any already known
+    fn add_nodes(&mut self, nodes: Vec<Url>) {
+        for node in nodes {
+            if !self.nodes.contains(&node) {
+                debug!("Elasticrud.add_nodes - sniffed {}", node);
+                self.nodes.push(node);
+            }
+        }
+    }
 
+    // The order in which to try the nodes: the preferred node first, then
+    // those after it, wrapping around
+    fn node_order(&self) -> Vec<usize> {
+        let preferred = self.preferred.get();
+        (0..self.nodes.len()).map(|i| (preferred + i) % self.nodes.len()).collect()
     }
 
-    // Build a uri given the resource
-    fn get_route(&self, resource: &str) -> String {
-        format!("http://{}:{}/{}", self.host, self.port, resource)
+    // Send a request, carrying the connection's credentials and an optional
+    // json body, to the first node which answers. A node which cannot be
+    // reached is skipped, as is one which answers that it is unavailable
+    // (502, 503 or 504) to a GET. Any other request, a DELETE included, may
+    // already have been acted on once the connection was made, so it is not
+    // sent again. The node which answers becomes the preferred node for later
+    // requests.
+    fn send(&self, method: Method, route: &Route, body: Option<&serde_json::Value>) -> Result<Response, EcError> {
+        let repeatable = method == Method::GET;
+        let mut failures = Vec::new();
+        for n in self.node_order() {
+            let node = &self.nodes[n];
+            let mut request = self.connection.authorize(self.client.request(method.clone(), route.on(node)?));
+            if let Some(body) = body {
+                request = request.json(body);
+            }
+            match request.send() {
+                Ok(ref response) if repeatable && unavailable(response.status()) => {
+                    warn!("{} is unavailable ({}), trying the next node", node, response.status());
+                    failures.push(format!("{}: {}", node, response.status()));
+                },
+                Ok(response) => {
+                    if n != self.preferred.get() {
+                        warn!("failing over to {}", node);
+                        self.preferred.set(n);
+                    }
+                    info!("{} {} served by {}", method, route, node);
+                    return Ok(response);
+                },
+                Err(ref e) if repeatable || never_connected(e) => {
+                    warn!("{} could not be reached ({}), trying the next node", node, e);
+                    failures.push(format!("{}: {}", node, e));
+                },
+                Err(e) => return Err(EcError::ReqwestGetError(format!("{} {} failed on {}: {}", method, route, node, e))),
+            }
+        }
+        Err(EcError::ReqwestGetError(format!("no node could serve {} {}: {}", method, route, failures.join("; "))))
     }
 
 }
 
+// Whether a response status means that the node, rather than the request,
+// is at fault, so that another node may serve the request
+fn unavailable(status: StatusCode) -> bool {
+    status == StatusCode::BAD_GATEWAY
+        || status == StatusCode::SERVICE_UNAVAILABLE
+        || status == StatusCode::GATEWAY_TIMEOUT
+}
+
+// Whether a request failed before a connection to the node was made, so that
+// the node cannot have seen it
+fn never_connected(error: &reqwest::Error) -> bool {
+    error.get_ref()
+        .and_then(|e| e.downcast_ref::<hyper::Error>())
+        .is_some_and(|e| e.is_connect())
+}
+
+// build a comma separated string of indices. Each Index displays as its original
+// name when it was parsed from one, so requests target the indices elasticsearch
+// actually holds rather than names rebuilt from their parsed pieces.
+fn join_indices(indices: &[Index]) -> String {
+    indices.iter()
+        .map(|i| format!("{}",i))
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    // A node which answers a single request with the given status and body
+    fn node(status: &'static str, body: &'static str) -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0; 4096];
+            let _ = stream.read(&mut buf);
+            let reply = format!("HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                                status, body.len(), body);
+            stream.write_all(reply.as_bytes()).unwrap();
+        });
+        url(&format!("http://127.0.0.1:{}/", port))
+    }
+
+    // The address of a node which refuses connections
+    fn down() -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        url(&format!("http://127.0.0.1:{}/", port))
+    }
+
+    #[test]
+    fn routes_join_onto_the_base_url() {
+        let route = Route { segments: vec!["_cat".into(), "indices".into()], query: vec![("format".into(), "json".into())] };
+        assert_eq!(route.on(&url("http://localhost:9200/")).unwrap().as_str(), "http://localhost:9200/_cat/indices?format=json");
+        assert_eq!(route.to_string(), "/_cat/indices?format=json");
+    }
+
+    #[test]
+    fn routes_keep_the_path_prefix() {
+        let connection = Connection::default();
+        for base in &["https://gateway/es-prod/", "https://gateway/es-prod"] {
+            let base = url(base);
+            let ec = Elasticrud::new(std::slice::from_ref(&base), &connection, false).unwrap();
+            let route = ec.get_route(&["_snapshot", "backups", "_all"], &[]);
+            assert_eq!(route.on(&base).unwrap().as_str(), "https://gateway/es-prod/_snapshot/backups/_all");
+        }
+    }
+
+    #[test]
+    fn routes_encode_index_names() {
+        let connection = Connection::default();
+        let base = url("http://localhost:9200/");
+        let ec = Elasticrud::new(std::slice::from_ref(&base), &connection, false).unwrap();
+        let route = ec.get_route(&["logs#1 a/b-2018.10.02,other", "_close"], &[]);
+        assert_eq!(route.on(&base).unwrap().as_str(), "http://localhost:9200/logs%231%20a%2Fb-2018.10.02,other/_close");
+    }
+
+    #[test]
+    fn needs_a_node() {
+        assert!(Elasticrud::new(&[], &Connection::default(), false).is_err());
+    }
+
+    #[test]
+    fn sniffed_nodes_follow_the_configured_ones() {
+        let connection = Connection::default();
+        let mut ec = Elasticrud::new(&[url("http://a:9200/"), url("http://b:9200/")], &connection, false).unwrap();
+        ec.add_nodes(vec![url("http://b:9200/"), url("http://c:9200/")]);
+        let nodes = ec.nodes.iter().map(|n| n.as_str()).collect::<Vec<&str>>();
+        assert_eq!(nodes, vec!["http://a:9200/", "http://b:9200/", "http://c:9200/"]);
+        ec.preferred.set(1);
+        assert_eq!(ec.node_order(), vec![1, 2, 0]);
+    }
+
+    #[test]
+    fn fails_over_to_the_next_answering_node() {
+        let connection = Connection::default();
+        let nodes = vec![down(), node("503 Service Unavailable", "{}"), node("200 OK", "[]")];
+        let ec = Elasticrud::new(&nodes, &connection, false).unwrap();
+        assert!(ec.get_aliases().unwrap().is_empty());
+        assert_eq!(ec.preferred.get(), 2);
+    }
+
+    #[test]
+    fn errors_when_no_node_answers() {
+        let connection = Connection::default();
+        let ec = Elasticrud::new(&[down(), down()], &connection, false).unwrap();
+        assert!(ec.get_aliases().is_err());
+        assert_eq!(ec.preferred.get(), 0);
+    }
+
+    #[test]
+    fn does_not_resend_a_post_after_a_gateway_timeout() {
+        let connection = Connection::default();
+        let spare = TcpListener::bind("127.0.0.1:0").unwrap();
+        let nodes = vec![node("504 Gateway Timeout", "{}"), url(&format!("http://{}/", spare.local_addr().unwrap()))];
+        let ec = Elasticrud::new(&nodes, &connection, false).unwrap();
+        let route = ec.get_route(&["_reindex"], &[]);
+        let response = ec.send(Method::POST, &route, Some(&json!({}))).unwrap();
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+        spare.set_nonblocking(true).unwrap();
+        assert!(spare.accept().is_err());
+    }
+
+    #[test]
+    fn does_not_resend_a_delete_after_a_gateway_timeout() {
+        let connection = Connection::default();
+        let spare = TcpListener::bind("127.0.0.1:0").unwrap();
+        let nodes = vec![node("504 Gateway Timeout", "{}"), url(&format!("http://{}/", spare.local_addr().unwrap()))];
+        let ec = Elasticrud::new(&nodes, &connection, false).unwrap();
+        let route = ec.get_route(&["foo-2018.10.01"], &[]);
+        let response = ec.send(Method::DELETE, &route, None).unwrap();
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+        spare.set_nonblocking(true).unwrap();
+        assert!(spare.accept().is_err());
+    }
+
+    #[test]
+    fn fails_over_a_post_from_an_unreachable_node() {
+        let connection = Connection::default();
+        let ec = Elasticrud::new(&[down(), node("200 OK", "{}")], &connection, false).unwrap();
+        let route = ec.get_route(&["_reindex"], &[]);
+        assert!(ec.send(Method::POST, &route, Some(&json!({}))).unwrap().status().is_success());
+        assert_eq!(ec.preferred.get(), 1);
+    }
+
+    #[test]
+    fn sniffs_nodes_at_startup() {
+        let connection = Connection::default();
+        let seed = node("200 OK", r#"{"nodes": {"a": {"name": "client-01", "http": {"publish_address": "client-01/10.0.0.1:9200"}}}}"#);
+        let ec = Elasticrud::new(std::slice::from_ref(&seed), &connection, true).unwrap();
+        assert_eq!(ec.nodes, vec![seed, url("http://client-01:9200/")]);
+    }
+}
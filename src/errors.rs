@@ -19,4 +19,28 @@ pub enum EcError {
     /// Failure to find look up the specified variable in the environment
     #[fail(display = "{} not found in environment", _0)]
     EnvVarError(String),
-}
\ No newline at end of file
+    /// Failure to read the config file
+    #[fail(display = "unable to read config file {}", _0)]
+    ConfigReadError(String),
+    /// Failure to deserialize the config file
+    #[fail(display = "unable to parse config file: {}", _0)]
+    ConfigParseError(String),
+    /// An argument is out of range or inconsistent with another
+    #[fail(display = "invalid argument: {}", _0)]
+    InvalidArgument(String),
+    /// A snapshot could not be taken, or did not complete successfully
+    #[fail(display = "snapshot failed: {}", _0)]
+    SnapshotError(String),
+    /// Daily indices could not be rolled up into a monthly index
+    #[fail(display = "compact failed: {}", _0)]
+    CompactError(String),
+    /// The local state file could not be read or written
+    #[fail(display = "unable to access state file {}", _0)]
+    StateError(String),
+    /// The http client could not be set up with the configured certificates
+    #[fail(display = "unable to set up connection: {}", _0)]
+    ConnectionError(String),
+    /// Results could not be rendered in the requested output format
+    #[fail(display = "unable to format output: {}", _0)]
+    OutputError(String),
+}
@@ -0,0 +1,258 @@
+//! # holds.rs
+//!
+//! Holds protect indices from deletion, no matter how old they are, for
+//! the likes of legal holds and incident investigations. A hold names an
+//! exact index, or a basename (which may be a wildcard pattern, as in
+//! `floors`), a date range, or both. Holds come from the `protect` section
+//! of the config file, and from a local state file managed by the `protect`
+//! and `unprotect` commands.
+use chrono::naive::NaiveDate;
+use crate::errors::EcError;
+use crate::index::Index;
+use glob::Pattern;
+use std::{
+    fmt,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+/// A hold on one or more indices
+#[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Default)]
+pub struct Hold {
+    /// The exact name of a held index (eg foo-2018.10.02)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index: Option<String>,
+    /// A held basename, or basename wildcard pattern (eg `nginx*`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub basename: Option<String>,
+    /// The first day of a held date range
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<NaiveDate>,
+    /// The last day of a held date range
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<NaiveDate>,
+    /// Why the hold exists
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl Hold {
+    /// Check that the hold names either an exact index, or a basename and/or
+    /// date range, and that the range and basename pattern are well formed.
+    ///
+    /// # Returns
+    ///
+    /// * `()` if the hold is valid
+    /// * `EcError` otherwise
+    pub fn validate(&self) -> Result<(), EcError> {
+        let err = |msg: &str| Err(EcError::InvalidArgument(format!("hold {}: {}", self, msg)));
+        let ranged = self.from.is_some() || self.to.is_some();
+
+        if self.index.is_some() && (self.basename.is_some() || ranged) {
+            return err("an exact index may not be combined with a basename or date range");
+        }
+        if self.index.is_none() && self.basename.is_none() && !ranged {
+            return err("an index, basename, or date range is required");
+        }
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            if from > to {
+                return err("the range ends before it starts");
+            }
+        }
+        if let Some(ref basename) = self.basename {
+            if let Err(e) = Pattern::new(basename) {
+                return err(&format!("{}", e));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the hold applies to an index. A date range holds any index
+    /// whose period overlaps it.
+    ///
+    /// # Parameters
+    ///
+    /// * `idx` - The index
+    ///
+    /// # Returns
+    ///
+    /// * `bool`
+    pub fn holds(&self, idx: &Index) -> bool {
+        if let Some(ref index) = self.index {
+            return *index == idx.to_string();
+        }
+        if let Some(ref basename) = self.basename {
+            let matched = basename == idx.name()
+                || Pattern::new(basename).map(|p| p.matches(idx.name())).unwrap_or(false);
+            if !matched {
+                return false;
+            }
+        }
+        self.from.map(|from| idx.last_date() >= from).unwrap_or(true)
+            && self.to.map(|to| idx.date <= to).unwrap_or(true)
+    }
+
+    // whether two holds cover the same indices, regardless of their reasons
+    fn same_as(&self, other: &Hold) -> bool {
+        self.index == other.index && self.basename == other.basename
+            && self.from == other.from && self.to == other.to
+    }
+}
+
+impl fmt::Display for Hold {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut pieces = Vec::new();
+        if let Some(ref index) = self.index {
+            pieces.push(format!("index {}", index));
+        }
+        if let Some(ref basename) = self.basename {
+            pieces.push(format!("basename {}", basename));
+        }
+        if let Some(from) = self.from {
+            pieces.push(format!("from {}", from));
+        }
+        if let Some(to) = self.to {
+            pieces.push(format!("to {}", to));
+        }
+        if let Some(ref reason) = self.reason {
+            pieces.push(format!("({})", reason));
+        }
+        write!(f, "{}", pieces.join(" "))
+    }
+}
+
+/// The holds recorded in the local state file
+#[derive(Debug)]
+pub struct HoldStore {
+    path: PathBuf,
+    holds: Vec<Hold>,
+}
+
+impl HoldStore {
+    /// Load the holds from a state file. A missing file holds nothing.
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - Path to the json state file
+    ///
+    /// # Returns
+    ///
+    /// * `HoldStore` instance if successful
+    /// * `EcError` if the file cannot be read or parsed
+    pub fn load<P>(path: P) -> Result<HoldStore, EcError>
+    where
+        P: AsRef<Path>
+    {
+        let path = path.as_ref().to_path_buf();
+        let holds = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|e| EcError::StateError(format!("{}: {}", path.display(), e)))?,
+            Err(ref e) if e.kind() == ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(EcError::StateError(format!("{}: {}", path.display(), e))),
+        };
+        Ok(HoldStore { path, holds })
+    }
+
+    /// Write the holds back to the state file
+    ///
+    /// # Returns
+    ///
+    /// * `()` if successful
+    /// * `EcError` if the file cannot be written
+    pub fn save(&self) -> Result<(), EcError> {
+        let contents = serde_json::to_string_pretty(&self.holds)
+            .map_err(|e| EcError::StateError(format!("{}: {}", self.path.display(), e)))?;
+        fs::write(&self.path, contents)
+            .map_err(|e| EcError::StateError(format!("{}: {}", self.path.display(), e)))
+    }
+
+    /// The holds in the store
+    pub fn holds(&self) -> &[Hold] {
+        &self.holds
+    }
+
+    /// Add a hold, replacing the reason of any existing hold on the same
+    /// indices
+    ///
+    /// # Parameters
+    ///
+    /// * `hold` - The hold to add
+    ///
+    /// # Returns
+    ///
+    /// * `()` if successful
+    /// * `EcError` if the hold is invalid
+    pub fn add(&mut self, hold: Hold) -> Result<(), EcError> {
+        hold.validate()?;
+        self.holds.retain(|h| !h.same_as(&hold));
+        self.holds.push(hold);
+        self.holds.sort();
+        Ok(())
+    }
+
+    /// Remove any hold on the same indices as the supplied one, regardless
+    /// of its reason
+    ///
+    /// # Parameters
+    ///
+    /// * `hold` - The hold to remove
+    ///
+    /// # Returns
+    ///
+    /// * `bool` indicating whether a hold was removed
+    pub fn remove(&mut self, hold: &Hold) -> bool {
+        let before = self.holds.len();
+        self.holds.retain(|h| !h.same_as(hold));
+        self.holds.len() != before
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> Option<NaiveDate> {
+        NaiveDate::from_ymd_opt(y, m, d)
+    }
+
+    #[test]
+    fn index_hold_matches_exact_name() {
+        let hold = Hold { index: Some("foo-2018.10.02".to_string()), ..Hold::default() };
+        assert!(hold.holds(&Index::new("foo", 2018, 10, 2)));
+        assert!(!hold.holds(&Index::new("foo", 2018, 10, 3)));
+    }
+
+    #[test]
+    fn basename_and_range_hold_matches_overlapping_periods() {
+        let hold = Hold {
+            basename: Some("nginx*".to_string()),
+            from: date(2018, 10, 1),
+            to: date(2018, 10, 31),
+            ..Hold::default()
+        };
+        assert!(hold.holds(&Index::new("nginx-access", 2018, 10, 15)));
+        assert!(hold.holds(&Index::new_monthly("nginx-access", 2018, 10)));
+        assert!(!hold.holds(&Index::new("nginx-access", 2018, 11, 1)));
+        assert!(!hold.holds(&Index::new("audit", 2018, 10, 15)));
+    }
+
+    #[test]
+    fn invalid_holds_produce_errors() {
+        assert!(Hold::default().validate().is_err());
+        assert!(Hold { index: Some("foo-2018.10.02".to_string()), basename: Some("foo".to_string()), ..Hold::default() }
+            .validate().is_err());
+        assert!(Hold { from: date(2018, 11, 1), to: date(2018, 10, 1), ..Hold::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn store_replaces_and_removes_holds_regardless_of_reason() {
+        let mut store = HoldStore { path: PathBuf::from("unused"), holds: Vec::new() };
+        let hold = Hold { basename: Some("audit".to_string()), reason: Some("legal".to_string()), ..Hold::default() };
+        store.add(hold.clone()).unwrap();
+        store.add(Hold { reason: Some("legal hold 42".to_string()), ..hold.clone() }).unwrap();
+        assert_eq!(store.holds().len(), 1);
+        assert!(store.remove(&Hold { reason: None, ..hold }));
+        assert!(store.holds().is_empty());
+    }
+}
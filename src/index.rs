@@ -5,8 +5,8 @@
 //! in logstash.
 
 use chrono::{
-    naive::NaiveDate,
-    Utc,
+    naive::{NaiveDate, NaiveDateTime},
+    Duration,
     Datelike
 };
 use crate::errors::EcError;
@@ -19,20 +19,51 @@ use std::{
 };
 use std::cmp::{PartialEq, Ordering}; // nested imports coming soon to rust
 
+/// The span of time covered by a dated index
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Granularity {
+    /// An hour's worth of data, eg ```NAME-YYYY.MM.DD.HH```. Holds the hour.
+    Hourly(u32),
+    /// A day's worth of data, eg ```NAME-YYYY.MM.DD```
+    Daily,
+    /// An ISO week's worth of data, eg ```NAME-YYYY.wWW```
+    Weekly,
+    /// A month's worth of data, eg ```NAME-YYYY.MM```
+    Monthly,
+    /// A member of a rollover series, eg ```NAME-000042```, which holds
+    /// data from its creation until it is rolled over. Holds the generation;
+    /// the date of the index is the day it was created.
+    Rollover(u32),
+}
+
 /// The Index struct is designed to handle dated indices
-/// of the form ```NAME-YYYY.MM.DD```
+/// of the form ```NAME-YYYY.MM.DD```, as well as hourly
+/// (```NAME-YYYY.MM.DD.HH```), weekly (```NAME-YYYY.wWW```), and
+/// monthly (```NAME-YYYY.MM```) indices, or indices named after a
+/// user supplied pattern (see `pattern.rs`). It also handles rollover
+/// indices (```NAME-000042```), whose date comes from their creation date
+/// rather than their name.
 /// The Index provides methods to create, sort, and present
 /// the Index.
-#[derive( Eq, Debug )]
+#[derive( Eq, Debug, Clone )]
 pub struct Index {
     pub name: String,
+    /// The first day covered by the index
     pub date: NaiveDate,
+    pub granularity: Granularity,
+    /// The naming pattern the index was parsed with (eg `{base}_%Y%m%d`),
+    /// if it is not named after the default ```NAME-YYYY.MM.DD``` form.
+    pub pattern: Option<String>,
+    /// The name of the index exactly as elasticsearch reported it, if the
+    /// Index was parsed from one. Display uses it in preference to rebuilding
+    /// the name from its pieces.
+    pub raw: Option<String>,
 }
 
 // Trait Impls for Comparisions
 impl PartialEq for Index {
     fn eq(&self, other: &Index) -> bool {
-        self.name == other.name && self.date == other.date
+        self.cmp(other) == Ordering::Equal
     }
 }
 
@@ -46,7 +77,11 @@ impl Ord for Index {
     fn cmp(&self, other: &Index) -> Ordering {
         // if the names match then we compare on the date field
         if self.name == other.name {
+            // indices whose names differ only in formatting (eg foo-2018.w5
+            // and foo-2018.w05) are nonetheless distinct indices
             self.date.cmp(&other.date)
+                .then(self.granularity.cmp(&other.granularity))
+                .then_with(|| self.cmp_names(other))
         } else {
             // otherwise, we simply compare on the name field
             self.name.cmp(&other.name)
@@ -54,10 +89,28 @@ impl Ord for Index {
     }
 }
 
-// return a string formatted thusly: ```base-YYYY.MM.DD```
+// return the original name of the index if it is known. Otherwise, return a string
+// formatted thusly: ```base-YYYY.MM.DD```, ```base-YYYY.MM.DD.HH```, ```base-YYYY.wWW```,
+// ```base-YYYY.MM```, or ```base-NNNNNN```, or after the index's pattern if it has one
 impl Display for Index {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}-{}", self.name, self.date.to_string().replace("-","."))
+        if let Some(ref raw) = self.raw {
+            return write!(f, "{}", raw);
+        }
+        if let Some(ref pattern) = self.pattern {
+            let start = self.start();
+            let pieces = pattern.split("{base}")
+                .map(|piece| start.format(piece).to_string())
+                .collect::<Vec<String>>();
+            return write!(f, "{}", pieces.join(&self.name));
+        }
+        match self.granularity {
+            Granularity::Hourly(hour) => write!(f, "{}-{}.{:02}", self.name, self.date.format("%Y.%m.%d"), hour),
+            Granularity::Daily => write!(f, "{}-{}", self.name, self.date.format("%Y.%m.%d")),
+            Granularity::Weekly => write!(f, "{}-{}", self.name, self.date.format("%G.w%V")),
+            Granularity::Monthly => write!(f, "{}-{}", self.name, self.date.format("%Y.%m")),
+            Granularity::Rollover(generation) => write!(f, "{}-{:06}", self.name, generation),
+        }
     }
 }
 // TODO: pick a better default. Maybe the minimum date supported
@@ -66,11 +119,26 @@ impl Default for Index {
         Index {
             name: String::new(),
             date: NaiveDate::from_ymd(2000, 1,1),
+            granularity: Granularity::Daily,
+            pattern: None,
+            raw: None,
         }
     }
 }
 
 impl Index {
+    // Compare the names of two indices of the same basename and period. Those
+    // parsed from a name compare by it, and those built the same way are
+    // named alike; only an index parsed from a name and one built from its
+    // pieces need their names formatting to tell them apart.
+    fn cmp_names(&self, other: &Index) -> Ordering {
+        match (&self.raw, &other.raw) {
+            (Some(a), Some(b)) => a.cmp(b),
+            (None, None) if self.pattern == other.pattern => Ordering::Equal,
+            _ => self.to_string().cmp(&other.to_string()),
+        }
+    }
+
     // TODO: change year, month, day into u8 and return result
     /// Given a base name, year, month, and day, new up an Index
     ///
@@ -89,7 +157,33 @@ impl Index {
     {
         Index {
             name:  name.into(),
-            date: NaiveDate::from_ymd(year, month, day)
+            date: NaiveDate::from_ymd(year, month, day),
+            granularity: Granularity::Daily,
+            pattern: None,
+            raw: None,
+        }
+    }
+
+    /// Given a base name, year, and month, new up a monthly Index
+    ///
+    /// # Parameters
+    ///
+    /// * `name`  - The base name of the Index
+    /// * `year`  - The year of the index (e.g. 2018)
+    /// * `month` - The month of the index (e.g. 4)
+    ///
+    /// # Returns
+    ///
+    /// * `Index` instance
+    pub fn new_monthly<I>(name: I, year: i32, month: u32) -> Index
+    where I: Into<String>
+    {
+        Index {
+            name:  name.into(),
+            date: NaiveDate::from_ymd_opt(year, month, 1).expect("invalid year or month"),
+            granularity: Granularity::Monthly,
+            pattern: None,
+            raw: None,
         }
     }
 
@@ -110,6 +204,90 @@ impl Index {
         IndexParser::parse(name)
     }
 
+    /// Given a base name, the first day of the period the index covers, and its
+    /// granularity, new up an Index. For weekly indices, the date is the Monday
+    /// of the ISO week; for monthly ones, the first of the month.
+    ///
+    /// # Parameters
+    ///
+    /// * `name`        - The base name of the Index
+    /// * `date`        - The first day covered by the index
+    /// * `granularity` - The span of time the index covers
+    ///
+    /// # Returns
+    ///
+    /// * `Index` instance
+    pub fn from_period<I>(name: I, date: NaiveDate, granularity: Granularity) -> Index
+    where I: Into<String>
+    {
+        Index {
+            name: name.into(),
+            date,
+            granularity,
+            pattern: None,
+            raw: None,
+        }
+    }
+
+    /// Given the name of a rollover index and the day it was created, new up
+    /// an Index. The name must end in a dash and a generation number of at
+    /// least six digits, as created by the rollover api.
+    ///
+    /// # Parameters
+    ///
+    /// * `input`   - The name of the index (eg app-logs-000042)
+    /// * `created` - The day the index was created
+    ///
+    /// # Returns
+    ///
+    /// * `Index` instance if successful
+    /// * `EcError` instance if the name does not end in a generation number
+    pub fn from_rollover(input: &str, created: NaiveDate) -> Result<Index, EcError> {
+        let (name, generation) = split_generation(input)
+            .ok_or_else(|| EcError::ParseError(format!("{} is not a rollover index", input)))?;
+
+        let mut idx = Index::from_period(name, created, Granularity::Rollover(generation));
+        idx.raw = Some(input.to_string());
+        Ok(idx)
+    }
+
+    /// Given the name of a data stream's backing index, new up an Index whose
+    /// base name is the stream. Backing indices are named
+    /// ```.ds-<stream>-YYYY.MM.DD-NNNNNN```, or ```.ds-<stream>-NNNNNN``` prior
+    /// to elasticsearch 7.11, in which case the creation date stands in for
+    /// the missing one.
+    ///
+    /// # Parameters
+    ///
+    /// * `input`   - The name of the index (eg .ds-app-logs-2018.10.02-000042)
+    /// * `created` - Optional day the index was created
+    ///
+    /// # Returns
+    ///
+    /// * `Index` instance if successful
+    /// * `EcError` instance if the name is not that of a backing index
+    pub fn from_backing(input: &str, created: Option<NaiveDate>) -> Result<Index, EcError> {
+        let err = || EcError::ParseError(format!("{} is not a data stream backing index", input));
+        let rest = input.strip_prefix(".ds-").ok_or_else(err)?;
+        let (stream, generation) = split_generation(rest).ok_or_else(err)?;
+
+        let dated = stream.rfind('-')
+            .and_then(|dash| {
+                NaiveDate::parse_from_str(&stream[dash + 1..], "%Y.%m.%d").ok()
+                    .map(|date| (&stream[..dash], date))
+            })
+            .filter(|(stream, _)| !stream.is_empty());
+        let (stream, date) = match (dated, created) {
+            (Some(dated), _) => dated,
+            (None, Some(created)) => (stream, created),
+            (None, None) => return Err(err()),
+        };
+
+        let mut idx = Index::from_period(stream, date, Granularity::Rollover(generation));
+        idx.raw = Some(input.to_string());
+        Ok(idx)
+    }
+
     /// Given &str components, return a result that is either an index isntance or a ParseIntError
     ///
     /// # Parameters
@@ -132,10 +310,35 @@ impl Index {
         let day = day.parse::<u32>()?;
         Ok(Index {
             name: name.into(),
-            date: NaiveDate::from_ymd(year, month, day)
+            date: NaiveDate::from_ymd(year, month, day),
+            granularity: Granularity::Daily,
+            pattern: None,
+            raw: None,
         })
     }
 
+    /// Given &str components, return a result that is either a monthly index
+    /// instance or a ParseIntError
+    ///
+    /// # Parameters
+    ///
+    /// * `name`  - Base name of the index (eg foo)
+    /// * `year`  - Year of index as &str
+    /// * `month` - Month of the index as &str
+    ///
+    /// # Returns Result Containing:
+    ///
+    /// * `Index` instance if successful
+    /// * `ParseIntError` if unsuccessful
+    pub fn from_month_strs<I>(name: I, year: &str, month: &str) -> Result<Index, ParseIntError>
+    where
+        I: Into<String>
+    {
+        let year = year.parse::<i32>()?;
+        let month = month.parse::<u32>()?;
+        Ok(Index::new_monthly(name, year, month))
+    }
+
     /// Get a reference to a str representing the base name of the Index
     ///
     /// # Parameters
@@ -162,7 +365,7 @@ impl Index {
         &self.date
     }
 
-    /// Return the number of days old the index is
+    /// Get the instant at which the period covered by the Index begins
     ///
     /// # Parameters
     ///
@@ -170,15 +373,65 @@ impl Index {
     ///
     /// # Returns
     ///
-    /// * `i64` indicating the number of days old the index is
-    pub fn days(&self) -> i64 {
-        let now = Utc::now();
-        let now_naive = NaiveDate::from_ymd(now.year(), now.month(), now.day());
-        let offset = now_naive.signed_duration_since(*self.date());
-        offset.num_days()
+    /// * `NaiveDateTime` instance
+    pub fn start(&self) -> NaiveDateTime {
+        let hour = match self.granularity {
+            Granularity::Hourly(hour) => hour,
+            _ => 0,
+        };
+        self.date.and_hms_opt(hour, 0, 0).unwrap_or_else(|| self.date.and_time(Default::default()))
+    }
+
+    /// Get the instant at which the period covered by the Index ends. The
+    /// period is half open; the end is the first instant not covered.
+    ///
+    /// # Parameters
+    ///
+    /// None
+    ///
+    /// # Returns
+    ///
+    /// * `NaiveDateTime` instance
+    pub fn end(&self) -> NaiveDateTime {
+        match self.granularity {
+            Granularity::Hourly(_) => self.start() + Duration::hours(1),
+            _ => (self.last_date() + Duration::days(1)).and_time(Default::default()),
+        }
     }
 
-    /// Return the number of days since a Datelike input as an i64
+    /// Get the last day covered by the Index. This is the same as `date`
+    /// for hourly, daily, and rollover indices, the Sunday of the week for weekly ones,
+    /// and the last day of the month for monthly ones.
+    ///
+    /// # Parameters
+    ///
+    /// None
+    ///
+    /// # Returns
+    ///
+    /// * `NaiveDate` instance
+    pub fn last_date(&self) -> NaiveDate {
+        match self.granularity {
+            Granularity::Hourly(_) | Granularity::Daily | Granularity::Rollover(_) => self.date,
+            Granularity::Weekly => self.date + Duration::days(6),
+            Granularity::Monthly => {
+                let (year, month) = if self.date.month() == 12 {
+                    (self.date.year() + 1, 1)
+                } else {
+                    (self.date.year(), self.date.month() + 1)
+                };
+                NaiveDate::from_ymd_opt(year, month, 1)
+                    .and_then(|d| d.pred_opt())
+                    .unwrap_or(self.date)
+            },
+        }
+    }
+
+    /// Return the number of days old the index is as of a Datelike input, as
+    /// an i64. The age is measured from the last day the index covers, so
+    /// that a weekly or monthly index is as young as the most recent data it
+    /// may hold. Rollover indices are aged from the day they were created.
+    /// `CmdProcessor::age` supplies the date from its clock.
     ///
     /// # Parameters
     ///
@@ -193,11 +446,22 @@ impl Index {
         D: Datelike
     {
         let nd = NaiveDate::from_ymd(from_date.year(), from_date.month(), from_date.day());
-        let offset = nd.signed_duration_since(*self.date());
+        let offset = nd.signed_duration_since(self.last_date());
         offset.num_days()
     }
 }
 
+// split a name ending in a dash and a generation number of at least six
+// digits, as created by the rollover api, into its prefix and generation
+fn split_generation(input: &str) -> Option<(&str, u32)> {
+    let dash = input.rfind('-')?;
+    let (name, generation) = (&input[..dash], &input[dash + 1..]);
+    if name.is_empty() || generation.len() < 6 || !generation.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    generation.parse::<u32>().ok().map(|generation| (name, generation))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -207,7 +471,10 @@ mod tests {
         let id = Index::new("foo", 2018, 2, 4);
         let expected = Index {
             name: "foo".to_string(),
-            date: NaiveDate::from_ymd(2018, 2, 4)
+            date: NaiveDate::from_ymd(2018, 2, 4),
+            granularity: Granularity::Daily,
+            pattern: None,
+            raw: None,
         };
         assert_eq!(id, expected);
     }
@@ -218,7 +485,10 @@ mod tests {
         let id = Index::new("foo", 0, 0, 0);
         let expected = Index {
             name: "foo".to_string(),
-            date: NaiveDate::from_ymd(0, 0, 0)
+            date: NaiveDate::from_ymd(0, 0, 0),
+            granularity: Granularity::Daily,
+            pattern: None,
+            raw: None,
         };
         assert_eq!(id, expected);
     }
@@ -228,7 +498,10 @@ mod tests {
         let id = Index::from_str("foo-2018.02.04");
         let expected = Index {
             name: "foo".to_string(),
-            date: NaiveDate::from_ymd(2018, 2, 4)
+            date: NaiveDate::from_ymd(2018, 2, 4),
+            granularity: Granularity::Daily,
+            pattern: None,
+            raw: None,
         };
         assert_eq!(id, Ok(expected));
     }
@@ -239,7 +512,10 @@ mod tests {
         let id = Index::from_str("foo-1.2.3-2018.02.04");
         let expected = Index {
             name: "foo-1.2.3".to_string(),
-            date: NaiveDate::from_ymd(2018, 2, 4)
+            date: NaiveDate::from_ymd(2018, 2, 4),
+            granularity: Granularity::Daily,
+            pattern: None,
+            raw: None,
         };
         assert_eq!(id, Ok(expected));
     }
@@ -250,7 +526,10 @@ mod tests {
         let id = Index::from_strs("foo", "2018", "02", "04");
         let expected = Index {
             name: "foo".to_string(),
-            date: NaiveDate::from_ymd(2018, 2, 4)
+            date: NaiveDate::from_ymd(2018, 2, 4),
+            granularity: Granularity::Daily,
+            pattern: None,
+            raw: None,
         };
         assert_eq!(id, Ok(expected));
     }
@@ -295,6 +574,131 @@ mod tests {
         assert_eq!(days, -1);
     }
 
+    #[test]
+    fn monthly_index_display() {
+        let id = Index::new_monthly("foo", 2018, 2);
+        assert_eq!(format!("{}", id), "foo-2018.02".to_string());
+    }
+
+    #[test]
+    fn monthly_index_last_date() {
+        assert_eq!(Index::new_monthly("foo", 2018, 2).last_date(), NaiveDate::from_ymd_opt(2018, 2, 28).unwrap());
+        assert_eq!(Index::new_monthly("foo", 2018, 12).last_date(), NaiveDate::from_ymd_opt(2018, 12, 31).unwrap());
+    }
+
+    #[test]
+    fn monthly_index_days_since_end_of_month() {
+        let id = Index::new_monthly("foo", 2018, 2);
+        let fd = NaiveDate::from_ymd_opt(2018,3,4).unwrap();
+        assert_eq!(id.days_since(&fd), 4);
+    }
+
+    fn at(year: i32, month: u32, day: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap().and_hms_opt(hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn weekly_index_display() {
+        let id = Index::from_period("foo", NaiveDate::from_isoywd_opt(2018, 42, chrono::Weekday::Mon).unwrap(), Granularity::Weekly);
+        assert_eq!(format!("{}", id), "foo-2018.w42".to_string());
+    }
+
+    #[test]
+    fn hourly_index_display() {
+        let id = Index::from_period("foo", NaiveDate::from_ymd_opt(2018, 10, 2).unwrap(), Granularity::Hourly(3));
+        assert_eq!(format!("{}", id), "foo-2018.10.02.03".to_string());
+    }
+
+    #[test]
+    fn weekly_index_period() {
+        let id = Index::from_str("foo-2018.w42").unwrap();
+        assert_eq!(id.start(), at(2018, 10, 15, 0));
+        assert_eq!(id.end(), at(2018, 10, 22, 0));
+        assert_eq!(id.days_since(&NaiveDate::from_ymd_opt(2018, 10, 22).unwrap()), 1);
+    }
+
+    #[test]
+    fn hourly_index_period() {
+        let id = Index::from_str("foo-2018.10.02.23").unwrap();
+        assert_eq!(id.start(), at(2018, 10, 2, 23));
+        assert_eq!(id.end(), at(2018, 10, 3, 0));
+    }
+
+    #[test]
+    fn monthly_index_period() {
+        let id = Index::from_str("foo-2018.12").unwrap();
+        assert_eq!(id.start(), at(2018, 12, 1, 0));
+        assert_eq!(id.end(), at(2019, 1, 1, 0));
+    }
+
+    #[test]
+    fn monthly_index_ne_daily() {
+        let id = Index::from_str("foo-2018.02");
+        let id2= Index::from_str("foo-2018.02.01");
+        assert_ne!(id, id2);
+    }
+
+    #[test]
+    fn index_display_prefers_raw_name() {
+        let id = Index::from_str("foo-2018.w5").unwrap();
+        assert_eq!(format!("{}", id), "foo-2018.w5".to_string());
+        assert_ne!(id, Index::from_str("foo-2018.w05").unwrap());
+    }
+
+    #[test]
+    fn parsed_index_eq_constructed() {
+        let id = Index::from_str("foo-2018.02.04").unwrap();
+        assert_eq!(id, Index::new("foo", 2018, 2, 4));
+    }
+
+    #[test]
+    fn rollover_index_from_name_and_creation_date() {
+        let created = NaiveDate::from_ymd_opt(2018, 10, 2).unwrap();
+        let id = Index::from_rollover("app-logs-000042", created).unwrap();
+        assert_eq!(id.name(), "app-logs");
+        assert_eq!(id.granularity, Granularity::Rollover(42));
+        assert_eq!(id.last_date(), created);
+        assert_eq!(id.days_since(&NaiveDate::from_ymd_opt(2018, 10, 12).unwrap()), 10);
+        assert_eq!(format!("{}", id), "app-logs-000042");
+    }
+
+    #[test]
+    fn rollover_index_requires_generation() {
+        let created = NaiveDate::from_ymd_opt(2018, 10, 2).unwrap();
+        assert!(Index::from_rollover("app-logs", created).is_err());
+        assert!(Index::from_rollover("app-logs-42", created).is_err());
+        assert!(Index::from_rollover("-000042", created).is_err());
+        assert!(Index::from_rollover("app-logs-2018.10.02", created).is_err());
+    }
+
+    #[test]
+    fn backing_index_takes_date_from_name() {
+        let created = NaiveDate::from_ymd_opt(2018, 10, 5).unwrap();
+        let id = Index::from_backing(".ds-app-logs-2018.10.02-000042", Some(created)).unwrap();
+        assert_eq!(id.name(), "app-logs");
+        assert_eq!(id.date(), &NaiveDate::from_ymd_opt(2018, 10, 2).unwrap());
+        assert_eq!(id.granularity, Granularity::Rollover(42));
+        assert_eq!(format!("{}", id), ".ds-app-logs-2018.10.02-000042");
+    }
+
+    #[test]
+    fn undated_backing_index_takes_creation_date() {
+        let created = NaiveDate::from_ymd_opt(2018, 10, 5).unwrap();
+        let id = Index::from_backing(".ds-app-logs-000042", Some(created)).unwrap();
+        assert_eq!(id.name(), "app-logs");
+        assert_eq!(id.date(), &created);
+        assert!(Index::from_backing(".ds-app-logs-000042", None).is_err());
+        assert!(Index::from_backing("app-logs-2018.10.02-000042", Some(created)).is_err());
+    }
+
+    #[test]
+    fn rollover_indices_order_by_generation() {
+        let created = NaiveDate::from_ymd_opt(2018, 10, 2).unwrap();
+        let older = Index::from_rollover("app-logs-000009", created).unwrap();
+        let newer = Index::from_rollover("app-logs-000010", created).unwrap();
+        assert!(older < newer);
+    }
+
     #[test]
     fn index_lt() {
         let id = Index::from_str("foo-2018.02.05");
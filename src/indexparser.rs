@@ -4,8 +4,9 @@
 //! Pest transform a PEG grammar into a struct. The grammar file is located
 //! with the source code, and named ```index.pest```
 //!
+use chrono::{naive::NaiveDate, Weekday};
 use pest::Parser;
-use crate::index::Index;
+use crate::index::{Granularity, Index};
 use crate::errors::EcError;
 
 // The pest parser is not exposed directly.
@@ -20,12 +21,14 @@ struct _IndexParser;
 pub struct IndexParser;
 
 impl IndexParser {
-    /// parse an elasticsearch index, of the form ```name-YYYY.MM.DD``` and return
-    /// a Result - either an Ok Index instance, or an Err String.
+    /// parse an elasticsearch index, of the form ```name-YYYY.MM.DD```, ```name-YYYY.MM.DD.HH```,
+    /// ```name-YYYY.wWW```, or ```name-YYYY.MM``` and return a Result - either an Ok Index
+    /// instance, or an Err String.
     ///
     /// # Parameters
     ///
     /// * `input` - A reference to a str which should follow `name-YYYY.MM.DD` or `name-V[.V]-YYYY.MM.DD`
+    ///   (or the hourly, weekly, or monthly equivalent)
     ///
     /// # Results
     ///
@@ -40,20 +43,24 @@ impl IndexParser {
         let mut year = None;
         let mut month = None;
         let mut day = None;
+        let mut hour = None;
+        let mut week = None;
+        let mut kind = None;
 
         for idx_piece in index {
 
             // A idx_piece can be converted to an iterator of the tokens which make it up:
             for inner_idx_piece in idx_piece.into_inner() {
-                let inner_span = inner_idx_piece.clone().into_span();
+                let inner_span = inner_idx_piece.as_span();
 
                 match inner_idx_piece.as_rule() {
                     Rule::base => {
                         name = Some(inner_span.as_str());
                     },
-                    Rule::date => {
+                    Rule::date | Rule::hour_date | Rule::week_date | Rule::month_date => {
+                        kind = Some(inner_idx_piece.as_rule());
                         for date_piece in inner_idx_piece.into_inner() {
-                            let inner_span = date_piece.clone().into_span();
+                            let inner_span = date_piece.as_span();
                             match date_piece.as_rule() {
                                 Rule::year  => {
                                     year = Some(inner_span.as_str());
@@ -64,21 +71,46 @@ impl IndexParser {
                                 Rule::day   => {
                                     day = Some(inner_span.as_str());
                                 },
+                                Rule::hour  => {
+                                    hour = Some(inner_span.as_str());
+                                },
+                                Rule::week  => {
+                                    week = Some(inner_span.as_str());
+                                },
                                 _ => unreachable!()
                             }
                         }
                     },
+                    Rule::EOI => {},
                     _ => unreachable!()
                 };
             }
         }
 
-        if name.is_none() || year.is_none() || month.is_none() || day.is_none() {
-            return Err(EcError::ParseError(format!("Could not parse index: {}", input)))
-        }
+        let parse_err = || EcError::ParseError(format!("Could not parse index: {}", input));
+        let num = |piece: Option<&str>| -> Result<u32, EcError> {
+            piece.ok_or_else(parse_err)?
+                .parse::<u32>()
+                .map_err(|e| EcError::ParseError(format!("{}",e)))
+        };
+
+        let name = name.ok_or_else(parse_err)?;
+        let year = num(year)? as i32;
+        let (date, granularity) = match kind {
+            Some(Rule::hour_date) => (NaiveDate::from_ymd_opt(year, num(month)?, num(day)?),
+                                      Granularity::Hourly(num(hour)?)),
+            Some(Rule::date) => (NaiveDate::from_ymd_opt(year, num(month)?, num(day)?),
+                                 Granularity::Daily),
+            Some(Rule::week_date) => (NaiveDate::from_isoywd_opt(year, num(week)?, Weekday::Mon),
+                                      Granularity::Weekly),
+            Some(Rule::month_date) => (NaiveDate::from_ymd_opt(year, num(month)?, 1),
+                                       Granularity::Monthly),
+            _ => return Err(parse_err()),
+        };
+        let date = date.ok_or_else(|| EcError::ParseError(format!("Invalid date in index: {}", input)))?;
 
-        let idx = Index::from_strs(name.unwrap(), year.unwrap(), month.unwrap(), day.unwrap())
-                  .map_err(|e| EcError::ParseError(format!("{}",e)))?;
+        let mut idx = Index::from_period(name, date, granularity);
+        idx.raw = Some(input.to_string());
 
         Ok(idx)
     }
@@ -88,14 +120,16 @@ impl IndexParser {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use chrono::naive::NaiveDate;
 
     #[test]
     fn can_parse_index_from_str() {
         let id = IndexParser::parse("foo-2018.02.22");
         let expected = Index {
             name: "foo".to_string(),
-            date: NaiveDate::from_ymd(2018, 2, 22)
+            date: NaiveDate::from_ymd(2018, 2, 22),
+            granularity: Granularity::Daily,
+            pattern: None,
+            raw: None,
         };
         assert_eq!(id, Ok(expected));
     }
@@ -105,11 +139,89 @@ mod tests {
         let id = IndexParser::parse("foo-1.2.3-2018.02.22");
         let expected = Index {
             name: "foo-1.2.3".to_string(),
-            date: NaiveDate::from_ymd(2018, 2, 22)
+            date: NaiveDate::from_ymd(2018, 2, 22),
+            granularity: Granularity::Daily,
+            pattern: None,
+            raw: None,
+        };
+        assert_eq!(id, Ok(expected));
+    }
+
+    #[test]
+    fn can_parse_monthly_index() {
+        let id = IndexParser::parse("metricbeat-2018.10");
+        let expected = Index {
+            name: "metricbeat".to_string(),
+            date: NaiveDate::from_ymd(2018, 10, 1),
+            granularity: Granularity::Monthly,
+            pattern: None,
+            raw: None,
+        };
+        assert_eq!(id, Ok(expected));
+    }
+
+    #[test]
+    fn can_parse_weekly_index() {
+        let id = IndexParser::parse("audit-2018.w42");
+        let expected = Index {
+            name: "audit".to_string(),
+            date: NaiveDate::from_ymd_opt(2018, 10, 15).unwrap(),
+            granularity: Granularity::Weekly,
+            pattern: None,
+            raw: None,
+        };
+        assert_eq!(id, Ok(expected));
+    }
+
+    #[test]
+    fn can_parse_hourly_index() {
+        let id = IndexParser::parse("nginx-2018.10.02.13");
+        let expected = Index {
+            name: "nginx".to_string(),
+            date: NaiveDate::from_ymd_opt(2018, 10, 2).unwrap(),
+            granularity: Granularity::Hourly(13),
+            pattern: None,
+            raw: None,
         };
         assert_eq!(id, Ok(expected));
     }
 
+    #[test]
+    fn parsed_indices_round_trip_exactly() {
+        let names = [
+            "foo-2018.02.22",
+            "foo-1.2.3-2018.02.22",
+            "nginx-2018.10.02.13",
+            "audit-2018.w42",
+            "audit-2018.w5",
+            "audit-2018.w05",
+            "metricbeat-2018.10",
+            "winlogbeat-6.4.0-2018.10",
+        ];
+        for name in names.iter() {
+            let id = IndexParser::parse(name).unwrap();
+            assert_eq!(format!("{}", id), name.to_string());
+        }
+    }
+
+    #[test]
+    fn out_of_range_week_produces_error() {
+        let id = IndexParser::parse("audit-2018.w54");
+        assert!(id.is_err());
+    }
+
+    #[test]
+    fn out_of_range_hour_produces_error() {
+        let id = IndexParser::parse("nginx-2018.10.02.24");
+        assert!(id.is_err());
+    }
+
+    #[test]
+    fn invalid_day_of_month_produces_error() {
+        let id = IndexParser::parse("foo-2018.02.30");
+        assert!(id.is_err());
+    }
+
     #[test]
     fn out_of_range_month_produces_error() {
         let id = IndexParser::parse("foo-2018.13.22");
@@ -7,7 +7,7 @@ use std::fmt::Display;
 use crate::traits::ElasticIndex;
 
 /// Deserializable struct modeling the Deprecate index
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Deprecate {
     callee: String,
     #[serde(rename = "env.DD_LOCATION")]
@@ -2,23 +2,43 @@
 #[macro_use] extern crate log;
 #[macro_use] extern crate pest_derive;
 #[macro_use] extern crate serde_derive;
+#[macro_use] extern crate serde_json;
 
 extern crate chrono;
+extern crate glob;
+extern crate hyper;
 extern crate pest;
+extern crate regex;
 extern crate reqwest;
 extern crate serde;
-extern crate serde_json;
+extern crate serde_yaml;
 
 //use log::Level;
 
+pub mod audit;
 pub mod cmds;
 pub mod cmdprocessor;
+pub mod clock;
+pub mod connection;
 pub(crate) mod constants;
+pub mod daterange;
 pub(crate) mod elasticrud;
 pub mod errors;
+pub mod holds;
 pub mod index;
 pub(crate) mod indexparser;
 pub mod indices;
+pub mod namefilter;
+pub mod pattern;
+pub mod rawalias;
+pub mod rawallocation;
+pub mod rawdatastream;
 pub mod rawindex;
+pub mod rawnodes;
+pub mod rawshard;
+pub mod rawsnapshot;
+pub mod rawtask;
+pub mod report;
+pub mod size;
 pub mod traits;
 pub mod config;
@@ -9,12 +9,18 @@ extern crate env_logger;
 extern crate failure;
 
 use elasticlean::{
+    clock::FixedClock,
     cmds::Cmds,
     cmdprocessor::CmdProcessor,
     config::Config,
+    daterange::{DateExpr, DateRange},
     errors::EcError,
+    holds::Hold,
+    namefilter::NameFilter,
+    report::OutputFormat,
 };
 
+use chrono::{naive::NaiveDate, Duration};
 use structopt::StructOpt;
 
 #[derive(StructOpt, Debug)]
@@ -32,14 +38,34 @@ use structopt::StructOpt;
 /// avoid logging of dependent libraries. Note that the idiomatic way of
 /// effecting log levels is to prefix the elasticlean command with RUST_LOG,
 /// eg RUST_LOG=elasticlean=debug elasticlean query -s 5 -o
-enum Opt {
+struct Opt {
+    #[structopt(long = "as-of", raw(global = "true"))]
+    /// Measure index ages as of this date (eg 2018-10-02) rather than
+    /// today, to simulate what a command would do on that date
+    as_of: Option<NaiveDate>,
+
+    #[structopt(subcommand)]
+    cmd: Command,
+}
+
+#[derive(StructOpt, Debug)]
+enum Command {
     #[structopt(name = "query")]
     /// query indices
     Query {
-        #[structopt(short = "n", long = "basename")]
+        #[structopt(short = "n", long = "basename", raw(conflicts_with_all = r#"&["pattern", "regex"]"#))]
         /// Specify the base name of the index. (sans date)
         name: Option<String>,
 
+        #[structopt(long = "pattern", conflicts_with = "regex")]
+        /// Match base names against a glob (eg 'winlogbeat-*')
+        pattern: Option<String>,
+
+        #[structopt(long = "regex")]
+        /// Match base names against a regular expression, which must
+        /// match the whole base name
+        regex: Option<String>,
+
         #[structopt(short = "s", long = "start")]
         /// Specify the number of days back you want to start
         start: Option<i32>,
@@ -48,9 +74,38 @@ enum Opt {
         /// Specify the number of days back you want to stop
         end: Option<i32>,
 
+        #[structopt(long = "before")]
+        /// Only indices ending before this date. Dates are either
+        /// YYYY-MM-DD, or a number of days (90d) or weeks (6w) ago
+        before: Option<DateExpr>,
+
+        #[structopt(long = "after")]
+        /// Only indices starting after this date
+        after: Option<DateExpr>,
+
+        #[structopt(long = "on")]
+        /// Only indices lying within this date
+        on: Option<DateExpr>,
+
+        #[structopt(long = "between")]
+        /// Only indices lying within these dates, inclusive
+        /// (eg 2018-03-01..2018-03-31)
+        between: Option<DateRange>,
+
         #[structopt(short = "o", long = "names-only")]
         /// Prints a list of unique base names of indices
         names_only: bool,
+
+        #[structopt(short = "S", long = "summary", conflicts_with = "names_only")]
+        /// Prints a rollup of each base name: the number of indices, their
+        /// oldest and newest dates, missing days, total size and worst health
+        summary: bool,
+
+        #[structopt(long = "format", default_value = "text")]
+        /// Output format: text, table, json or csv. The structured formats
+        /// list each index with its date, age, health, status, document
+        /// count and size, along with a summary
+        format: OutputFormat,
     },
     #[structopt(name = "process")]
     /// apply a process to indices
@@ -66,10 +121,85 @@ enum Opt {
         #[structopt(short = "e", long = "end")]
         /// Specify the number of days back you want to stop
         end: Option<i32>,
+
+        #[structopt(long = "format", default_value = "text")]
+        /// Output format: text, table, json or csv. The structured formats
+        /// list each index with its date, age, health, status, document
+        /// count and size, along with a summary
+        format: OutputFormat,
     },
     #[structopt(name = "delete")]
     /// query indices
     Delete {
+        #[structopt(short = "n", long = "basename", raw(required_unless_one = r#"&["pattern", "regex"]"#),
+                    raw(conflicts_with_all = r#"&["pattern", "regex"]"#))]
+        /// Specify the base name of the index. (sans date)
+        name: Option<String>,
+
+        #[structopt(long = "pattern", conflicts_with = "regex")]
+        /// Delete the indices of every base name matching a glob
+        /// (eg 'winlogbeat-*')
+        pattern: Option<String>,
+
+        #[structopt(long = "regex")]
+        /// Delete the indices of every base name matching a regular
+        /// expression, which must match the whole base name
+        regex: Option<String>,
+
+        #[structopt(short = "s", long = "start")]
+        /// Specify the number of days back you want to start
+        start: Option<i32>,
+
+        #[structopt(short = "e", long = "end",
+                    raw(required_unless_one = r#"&["max_size", "keep_last", "before", "after", "on", "between"]"#))]
+        /// Specify the number of days back you want to stop
+        end: Option<i32>,
+
+        #[structopt(long = "before")]
+        /// Only indices ending before this date. Dates are either
+        /// YYYY-MM-DD, or a number of days (90d) or weeks (6w) ago
+        before: Option<DateExpr>,
+
+        #[structopt(long = "after")]
+        /// Only indices starting after this date
+        after: Option<DateExpr>,
+
+        #[structopt(long = "on")]
+        /// Only indices lying within this date
+        on: Option<DateExpr>,
+
+        #[structopt(long = "between")]
+        /// Only indices lying within these dates, inclusive
+        /// (eg 2018-03-01..2018-03-31)
+        between: Option<DateRange>,
+
+        #[structopt(long = "max-size",
+                    raw(conflicts_with_all = r#"&["start", "end", "keep_last", "before", "after", "on", "between"]"#))]
+        /// Delete the oldest indices until the matching basenames fit within
+        /// this size (eg 500gb)
+        max_size: Option<String>,
+
+        #[structopt(long = "keep-last", raw(conflicts_with_all = r#"&["start", "end", "before", "after", "on", "between"]"#))]
+        /// Keep only this many of the most recent indices
+        keep_last: Option<usize>,
+
+        #[structopt(long = "snapshot-repo")]
+        /// Snapshot the indices into this repository before deleting them.
+        /// Nothing is deleted if the snapshot fails.
+        snapshot_repo: Option<String>,
+
+        #[structopt(short = "d", long = "dry-run")]
+        dry_run: bool,
+
+        #[structopt(long = "format", default_value = "text")]
+        /// Output format: text, table, json or csv. The structured formats
+        /// list each index with its date, age, health, status, document
+        /// count and size, along with a summary
+        format: OutputFormat,
+    },
+    #[structopt(name = "restore")]
+    /// restore indices from the snapshots in a repository
+    Restore {
         #[structopt(short = "n", long = "basename")]
         /// Specify the base name of the index. (sans date)
         name: String,
@@ -80,32 +210,230 @@ enum Opt {
 
         #[structopt(short = "e", long = "end")]
         /// Specify the number of days back you want to stop
-        end: i32,
+        end: Option<i32>,
+
+        #[structopt(short = "r", long = "repo")]
+        /// Specify the snapshot repository to restore from
+        repo: String,
+
+        #[structopt(long = "rename-prefix")]
+        /// Restore each index under its name with this prefix
+        rename_prefix: Option<String>,
+
+        #[structopt(short = "d", long = "dry-run")]
+        dry_run: bool,
+    },
+    #[structopt(name = "compact")]
+    /// roll a month of daily indices up into a monthly index
+    Compact {
+        #[structopt(short = "n", long = "basename")]
+        /// Specify the base name of the index. (sans date)
+        name: String,
+
+        #[structopt(short = "m", long = "month")]
+        /// Specify the month to compact, as YYYY.MM
+        month: String,
 
         #[structopt(short = "d", long = "dry-run")]
         dry_run: bool,
     },
+    #[structopt(name = "watermark")]
+    /// delete the oldest indices of the configured basenames when node
+    /// disk usage is too high
+    Watermark {
+        #[structopt(long = "high")]
+        /// Disk usage percentage above which indices are deleted
+        high: Option<u32>,
+
+        #[structopt(long = "low")]
+        /// Disk usage percentage to delete indices down to
+        low: Option<u32>,
+
+        #[structopt(short = "d", long = "dry-run")]
+        dry_run: bool,
+    },
+    #[structopt(name = "apply")]
+    /// delete indices according to the policies in the config file
+    /// named by ELASTICLEAN_CONFIG
+    Apply {
+        #[structopt(short = "d", long = "dry-run")]
+        dry_run: bool,
+    },
+
+    #[structopt(name = "audit")]
+    /// report days on which a basename has no index, indices dated in the
+    /// future, and days covered by more than one index (eg foo-1.2.3-2018.10.02
+    /// and foo-2018.10.02)
+    Audit {
+        #[structopt(short = "n", long = "basename", raw(conflicts_with_all = r#"&["pattern", "regex"]"#))]
+        /// Specify the base name of the index. (sans date)
+        name: Option<String>,
+
+        #[structopt(long = "pattern", conflicts_with = "regex")]
+        /// Audit every base name matching a glob (eg 'winlogbeat-*')
+        pattern: Option<String>,
+
+        #[structopt(long = "regex")]
+        /// Audit every base name matching a regular expression, which must
+        /// match the whole base name
+        regex: Option<String>,
+
+        #[structopt(long = "fail")]
+        /// Exit with status 2 if anything is found, for alerting
+        fail: bool,
+
+        #[structopt(long = "format", default_value = "text")]
+        /// Output format: text, table, json or csv
+        format: OutputFormat,
+    },
+
+    #[structopt(name = "streams")]
+    /// list data streams along with their backing indices. Query and
+    /// delete take a stream's name as the basename of its backing indices
+    Streams,
+
+    #[structopt(name = "protect")]
+    /// protect indices from deletion, by exact index, basename, date range,
+    /// or basename and date range. Holds are recorded in the state file named
+    /// by the config's state_file or ELASTICLEAN_STATE
+    Protect {
+        #[structopt(long = "index", raw(conflicts_with_all = r#"&["name", "from", "to"]"#))]
+        /// Hold a single index (eg foo-2018.10.02)
+        index: Option<String>,
+
+        #[structopt(short = "n", long = "basename")]
+        /// Hold the indices of a base name, or base name pattern (eg 'nginx*')
+        name: Option<String>,
+
+        #[structopt(long = "from")]
+        /// Hold indices covering this day or later (eg 2018-10-01)
+        from: Option<NaiveDate>,
+
+        #[structopt(long = "to")]
+        /// Hold indices covering this day or earlier (eg 2018-10-31)
+        to: Option<NaiveDate>,
+
+        #[structopt(long = "reason")]
+        /// Why the indices are held
+        reason: Option<String>,
+    },
+
+    #[structopt(name = "unprotect")]
+    /// remove a hold recorded by protect. The hold is identified by the same
+    /// index, basename, and date range it was recorded with
+    Unprotect {
+        #[structopt(long = "index", raw(conflicts_with_all = r#"&["name", "from", "to"]"#))]
+        /// The held index
+        index: Option<String>,
+
+        #[structopt(short = "n", long = "basename")]
+        /// The held base name, or base name pattern
+        name: Option<String>,
+
+        #[structopt(long = "from")]
+        /// The first day of the held range
+        from: Option<NaiveDate>,
+
+        #[structopt(long = "to")]
+        /// The last day of the held range
+        to: Option<NaiveDate>,
+    },
+
+    #[structopt(name = "holds")]
+    /// list the holds protecting indices from deletion
+    Holds,
 }
 
+// Combine the day offsets and date options shared by query and delete into
+// a single range
+fn date_range(start: Option<i32>, end: Option<i32>, before: Option<DateExpr>, after: Option<DateExpr>,
+              on: Option<DateExpr>, between: Option<DateRange>) -> DateRange {
+    let mut range = DateRange::from_offsets(start, end);
+    if let Some(date) = before {
+        range = range.before(date);
+    }
+    if let Some(date) = after {
+        range = range.after(date);
+    }
+    if let Some(date) = on {
+        range = range.on(date);
+    }
+    if let Some(between) = between {
+        range = range.and(between);
+    }
+    range
+}
 
 fn main() -> Result<(), EcError> {
     env_logger::init();
     debug!("logger initialized");
-    // create config from the environment
-    let config = Config::from_env()?;
+    let opt = Opt::from_args();
+    // create config from the config file, or the environment if there isn't one
+    let config = Config::load()?;
 
     // create command processor
     //let cproc = CmdProcessor::new(config.host.as_str(), config.port);
-    let cproc = CmdProcessor::new(&config);
+    let cproc = match opt.as_of {
+        Some(date) => {
+            // the first instant at which it is `date` in the config's timezone,
+            // once the grace window has passed
+            let now = config.timezone.start_of(date) + Duration::hours(config.grace_hours.into());
+            CmdProcessor::with_clock(&config, Box::new(FixedClock::new(now)))?
+        },
+        None => CmdProcessor::new(&config)?,
+    };
 
     // create cmds struct
     let cmds = Cmds::new(cproc);
 
     //let matches = Opt::from_args();
-    match Opt::from_args() {
-         Opt::Query{ name, start, end, names_only } => cmds.query(name, start, end, names_only),
-         Opt::Process{ name, start, end }           => cmds.process(name, start, end),
-         Opt::Delete{ name, start, end, dry_run }   => cmds.delete(name, start, end, dry_run),
+    match opt.cmd {
+         Command::Query{ name, pattern, regex, start, end, before, after, on, between, names_only, summary,
+                     format } => {
+             let range = date_range(start, end, before, after, on, between);
+             let name = NameFilter::from_options(name, pattern, regex)?;
+             if summary {
+                 cmds.summary(name, &range, format)
+             } else {
+                 cmds.query(name, &range, names_only, format)
+             }
+         },
+         Command::Process{ name, start, end, format }   => cmds.process(name, start, end, format),
+         Command::Delete{ name, pattern, regex, start, end, before, after, on, between, max_size, keep_last,
+                      snapshot_repo, dry_run, format } => {
+             let name = NameFilter::from_options(name, pattern, regex)?
+                 .expect("structopt requires basename, pattern or regex");
+             let snapshot_repo = snapshot_repo.as_deref();
+             match (max_size, keep_last) {
+                 (Some(max_size), _)     => cmds.delete_to_size(name, &max_size, snapshot_repo, dry_run, format),
+                 (None, Some(keep_last)) => cmds.delete_keep_last(name, keep_last, snapshot_repo, dry_run, format),
+                 (None, None)            => {
+                     let range = date_range(start, end, before, after, on, between);
+                     cmds.delete(name, &range, snapshot_repo, dry_run, format)
+                 },
+             }
+         },
+         Command::Restore{ name, start, end, repo, rename_prefix, dry_run } => {
+             cmds.restore(name, start, end, &repo, rename_prefix.as_deref(), dry_run)
+         },
+         Command::Compact{ name, month, dry_run }       => cmds.compact(name, &month, dry_run),
+         Command::Watermark{ high, low, dry_run }       => cmds.watermark(high, low, dry_run),
+         Command::Apply{ dry_run }                      => cmds.apply(dry_run),
+         Command::Audit{ name, pattern, regex, fail, format } => {
+             let found = cmds.audit(NameFilter::from_options(name, pattern, regex)?, format)?;
+             if fail && found > 0 {
+                 std::process::exit(2);
+             }
+             Ok(())
+         },
+         Command::Streams                               => cmds.streams(),
+         Command::Protect{ index, name, from, to, reason } => {
+             cmds.protect(Hold { index, basename: name, from, to, reason })
+         },
+         Command::Unprotect{ index, name, from, to }    => {
+             cmds.unprotect(&Hold { index, basename: name, from, to, reason: None })
+         },
+         Command::Holds                                 => cmds.holds(),
     }?;
 
     Ok(())
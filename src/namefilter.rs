@@ -0,0 +1,148 @@
+//! # namefilter.rs
+//!
+//! Selection of indices by basename. A basename may be matched exactly,
+//! against a glob (eg `winlogbeat-*`), or against a regular expression,
+//! so that a single command may span several series of indices.
+use crate::errors::EcError;
+use glob::Pattern;
+use regex::Regex;
+use std::fmt;
+
+/// Matches the basenames of indices
+#[derive(Debug, Clone)]
+pub enum NameFilter {
+    /// Matches a single basename
+    Exact(String),
+    /// Matches the basenames a glob matches, eg `winlogbeat-*`
+    Glob(Pattern),
+    /// Matches the basenames a regular expression matches in their entirety
+    Regex(Regex),
+}
+
+impl NameFilter {
+    /// Compile a glob into a NameFilter
+    ///
+    /// # Parameters
+    ///
+    /// * `glob` - The glob (eg `winlogbeat-*`)
+    ///
+    /// # Returns
+    ///
+    /// * `NameFilter` instance if successful
+    /// * `EcError` if the glob is malformed
+    pub fn glob(glob: &str) -> Result<NameFilter, EcError> {
+        Pattern::new(glob)
+            .map(NameFilter::Glob)
+            .map_err(|e| EcError::InvalidArgument(format!("pattern {}: {}", glob, e)))
+    }
+
+    /// Compile a regular expression into a NameFilter. The expression must
+    /// match the whole basename, as if it were anchored at both ends.
+    ///
+    /// # Parameters
+    ///
+    /// * `regex` - The regular expression (eg `winlogbeat-6\.\d+\.\d+`)
+    ///
+    /// # Returns
+    ///
+    /// * `NameFilter` instance if successful
+    /// * `EcError` if the expression is malformed
+    pub fn regex(regex: &str) -> Result<NameFilter, EcError> {
+        Regex::new(&format!("^(?:{})$", regex))
+            .map(NameFilter::Regex)
+            .map_err(|e| EcError::InvalidArgument(format!("regex {}: {}", regex, e)))
+    }
+
+    /// Build a NameFilter from whichever of the cli's basename, pattern and
+    /// regex options was supplied, if any
+    ///
+    /// # Parameters
+    ///
+    /// * `name`    - Optional exact basename
+    /// * `pattern` - Optional glob
+    /// * `regex`   - Optional regular expression
+    ///
+    /// # Returns
+    ///
+    /// * `Option<NameFilter>` if successful
+    /// * `EcError` if the pattern or regex is malformed, or more than one
+    ///   option is supplied
+    pub fn from_options(name: Option<String>, pattern: Option<String>, regex: Option<String>)
+    -> Result<Option<NameFilter>, EcError> {
+        match (name, pattern, regex) {
+            (None, None, None) => Ok(None),
+            (Some(name), None, None) => Ok(Some(NameFilter::Exact(name))),
+            (None, Some(pattern), None) => NameFilter::glob(&pattern).map(Some),
+            (None, None, Some(regex)) => NameFilter::regex(&regex).map(Some),
+            _ => Err(EcError::InvalidArgument(
+                "only one of basename, pattern, and regex may be supplied".to_string())),
+        }
+    }
+
+    /// Whether the filter matches a basename
+    pub fn matches(&self, name: &str) -> bool {
+        match self {
+            NameFilter::Exact(n) => n == name,
+            NameFilter::Glob(p) => p.matches(name),
+            NameFilter::Regex(r) => r.is_match(name),
+        }
+    }
+}
+
+impl From<String> for NameFilter {
+    fn from(name: String) -> Self {
+        NameFilter::Exact(name)
+    }
+}
+
+impl<'a> From<&'a str> for NameFilter {
+    fn from(name: &'a str) -> Self {
+        NameFilter::Exact(name.to_string())
+    }
+}
+
+impl fmt::Display for NameFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NameFilter::Exact(n) => write!(f, "{}", n),
+            NameFilter::Glob(p) => write!(f, "{}", p),
+            NameFilter::Regex(r) => write!(f, "/{}/", r),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exact_matches_only_the_basename() {
+        let filter = NameFilter::from("winlogbeat-6.2.4");
+        assert!(filter.matches("winlogbeat-6.2.4"));
+        assert!(!filter.matches("winlogbeat-6.4.0"));
+    }
+
+    #[test]
+    fn glob_spans_series() {
+        let filter = NameFilter::glob("winlogbeat-*").unwrap();
+        assert!(filter.matches("winlogbeat-6.2.4"));
+        assert!(filter.matches("winlogbeat-6.4.0"));
+        assert!(!filter.matches("metricbeat-6.4.0"));
+    }
+
+    #[test]
+    fn regex_matches_whole_basename() {
+        let filter = NameFilter::regex(r"winlogbeat-6\.\d+\.\d+").unwrap();
+        assert!(filter.matches("winlogbeat-6.2.4"));
+        assert!(!filter.matches("winlogbeat-6.2.4-old"));
+        assert!(!filter.matches("x-winlogbeat-6.2.4"));
+    }
+
+    #[test]
+    fn malformed_and_conflicting_options_produce_errors() {
+        assert!(NameFilter::glob("winlogbeat-[").is_err());
+        assert!(NameFilter::regex("winlogbeat-(").is_err());
+        assert!(NameFilter::from_options(Some("foo".into()), Some("foo*".into()), None).is_err());
+        assert!(NameFilter::from_options(None, None, None).unwrap().is_none());
+    }
+}
@@ -0,0 +1,442 @@
+//! # pattern.rs
+//!
+//! User defined index naming patterns, for indices which are not named
+//! after logstash's default ```NAME-YYYY.MM.DD``` form. A pattern is made
+//! up of the `{base}` placeholder, a subset of the strftime date specifiers,
+//! and literal text. For example:
+//!
+//! * `{base}_%Y%m%d`    - foo_20181002
+//! * `{base}-%Y-%m-%d`  - foo-2018-10-02
+//! * `%Y.%m.%d-{base}`  - 2018.10.02-foo
+//! * `{base}-%G.w%V`    - foo-2018.w40
+//!
+//! The granularity of the index follows from the finest specifier present:
+//! `%H` for hourly, `%d` for daily, `%V` for weekly, and `%m` for monthly.
+//!
+//! Each daily pattern has a monthly companion, which names the monthly
+//! indices its dailies are compacted into (eg `{base}_%Y%m` for
+//! `{base}_%Y%m%d`).
+use chrono::{naive::NaiveDate, Datelike, Weekday};
+use crate::config::Config;
+use crate::errors::EcError;
+use crate::index::{Granularity, Index};
+use crate::rawindex::RawIndex;
+use regex::Regex;
+use std::collections::HashSet;
+
+/// A compiled index naming pattern
+#[derive(Debug)]
+pub struct IndexPattern {
+    format: String,
+    base: Option<String>,
+    regex: Regex,
+    granularity: Granularity,
+}
+
+impl IndexPattern {
+    /// Compile a naming pattern. If a basename is supplied, the pattern only
+    /// matches indices with that basename.
+    ///
+    /// # Parameters
+    ///
+    /// * `format` - The naming pattern (eg `{base}_%Y%m%d`)
+    /// * `base`   - Optional basename the pattern is restricted to
+    ///
+    /// # Returns
+    ///
+    /// * `IndexPattern` instance if successful
+    /// * `EcError` if the pattern is malformed
+    pub fn new(format: &str, base: Option<&str>) -> Result<IndexPattern, EcError> {
+        let err = |msg: &str| EcError::ParseError(format!("index pattern {}: {}", format, msg));
+
+        if !format.contains("{base}") {
+            return Err(err("missing {base}"));
+        }
+
+        let mut re = String::from("^");
+        let mut rest = format;
+        while let Some(c) = rest.chars().next() {
+            if rest.starts_with("{base}") {
+                match base {
+                    Some(base) => re.push_str(&format!("(?P<base>{})", regex::escape(base))),
+                    None => re.push_str("(?P<base>.+?)"),
+                }
+                rest = &rest["{base}".len()..];
+                continue;
+            }
+            if c == '%' {
+                let spec = rest[1..].chars().next().ok_or_else(|| err("trailing %"))?;
+                let group = match spec {
+                    'Y' => "(?P<Y>\\d{4})",
+                    'G' => "(?P<G>\\d{4})",
+                    'm' => "(?P<m>\\d{2})",
+                    'd' => "(?P<d>\\d{2})",
+                    'H' => "(?P<H>\\d{2})",
+                    'V' => "(?P<V>\\d{2})",
+                    '%' => "%",
+                    _ => return Err(err(&format!("unsupported specifier %{}", spec))),
+                };
+                re.push_str(group);
+                rest = &rest[1 + spec.len_utf8()..];
+                continue;
+            }
+            re.push_str(&regex::escape(&c.to_string()));
+            rest = &rest[c.len_utf8()..];
+        }
+        re.push('$');
+
+        let regex = Regex::new(&re).map_err(|e| err(&format!("{}", e)))?;
+        let has = |name: &str| regex.capture_names().any(|n| n == Some(name));
+
+        let granularity = if has("H") && has("Y") && has("m") && has("d") {
+            Granularity::Hourly(0)
+        } else if has("d") && has("Y") && has("m") {
+            Granularity::Daily
+        } else if has("V") && (has("G") || has("Y")) {
+            Granularity::Weekly
+        } else if has("m") && has("Y") {
+            Granularity::Monthly
+        } else {
+            return Err(err("not enough date specifiers to identify a period"));
+        };
+
+        Ok(IndexPattern {
+            format: format.to_string(),
+            base: base.map(String::from),
+            regex,
+            granularity,
+        })
+    }
+
+    /// The monthly companion of a daily pattern: the pattern with its day of
+    /// the month, along with the separator which sets it apart, dropped.
+    ///
+    /// # Returns
+    ///
+    /// * `Some(IndexPattern)` naming monthly indices, for a daily pattern
+    /// * `None` for any other pattern
+    pub fn monthly(&self) -> Option<IndexPattern> {
+        if self.granularity != Granularity::Daily {
+            return None;
+        }
+        let day = self.format.find("%d")?;
+        let (before, after) = (&self.format[..day], &self.format[day + 2..]);
+        let separator = |c: char| !c.is_alphanumeric() && c != '%' && c != '{' && c != '}';
+        let format = match (before.chars().last(), after.chars().next()) {
+            // the separator ahead of the day, unless it ends a specifier (eg %m%d)
+            (Some(c), _) if separator(c) => format!("{}{}", &before[..before.len() - c.len_utf8()], after),
+            // the separator after a leading day (eg %d.%m.%Y-{base})
+            (None, Some(c)) if separator(c) => after[c.len_utf8()..].to_string(),
+            _ => format!("{}{}", before, after),
+        };
+        IndexPattern::new(&format, self.base.as_deref()).ok()
+    }
+
+    /// Parse an index name which follows the pattern
+    ///
+    /// # Parameters
+    ///
+    /// * `input` - The name of the index
+    ///
+    /// # Returns
+    ///
+    /// * `Some(Index)` if the name matches the pattern and holds a valid date
+    /// * `None` otherwise
+    pub fn parse(&self, input: &str) -> Option<Index> {
+        let caps = self.regex.captures(input)?;
+        let num = |name: &str| -> Option<u32> {
+            caps.name(name).and_then(|m| m.as_str().parse::<u32>().ok())
+        };
+        let year = || -> Option<i32> {
+            caps.name("Y").or_else(|| caps.name("G")).and_then(|m| m.as_str().parse::<i32>().ok())
+        };
+
+        let (date, granularity) = match self.granularity {
+            Granularity::Hourly(_) => {
+                let hour = num("H").filter(|h| *h < 24)?;
+                (NaiveDate::from_ymd_opt(year()?, num("m")?, num("d")?)?, Granularity::Hourly(hour))
+            },
+            Granularity::Daily => (NaiveDate::from_ymd_opt(year()?, num("m")?, num("d")?)?, Granularity::Daily),
+            Granularity::Weekly => {
+                (NaiveDate::from_isoywd_opt(year()?, num("V")?, Weekday::Mon)?, Granularity::Weekly)
+            },
+            Granularity::Monthly => (NaiveDate::from_ymd_opt(year()?, num("m")?, 1)?, Granularity::Monthly),
+            // rollover indices carry no date in their name
+            Granularity::Rollover(_) => return None,
+        };
+
+        let mut idx = Index::from_period(caps.name("base")?.as_str(), date, granularity);
+        idx.pattern = Some(self.format.clone());
+        idx.raw = Some(input.to_string());
+        Some(idx)
+    }
+}
+
+/// Parses index names using the naming patterns from the config, falling
+/// back on the default ```NAME-YYYY.MM.DD``` grammar. Patterns attached to a
+/// specific basename are tried before general ones.
+#[derive(Debug, Default)]
+pub struct IndexMatcher {
+    patterns: Vec<IndexPattern>,
+}
+
+impl IndexMatcher {
+    /// New up an IndexMatcher from the patterns in a Config. Patterns which
+    /// fail to compile are logged and skipped; `Config::from_file` rejects
+    /// them up front.
+    ///
+    /// # Parameters
+    ///
+    /// * `config` - Reference to the Config
+    ///
+    /// # Returns
+    ///
+    /// * `IndexMatcher` instance
+    pub fn new(config: &Config) -> IndexMatcher {
+        let attached = config.policies.iter()
+            .filter_map(|(name, policy)| policy.pattern.as_ref().map(|p| (p, Some(name.as_str()))));
+        let general = config.patterns.iter().map(|p| (p, None));
+
+        let mut patterns = attached.chain(general)
+            .filter_map(|(format, base)| {
+                IndexPattern::new(format, base)
+                    .map_err(|e| error!("skipping {}", e))
+                    .ok()
+            })
+            .collect::<Vec<IndexPattern>>();
+        // so that compacted monthly indices parse back
+        let companions = patterns.iter().filter_map(|p| p.monthly()).collect::<Vec<IndexPattern>>();
+        patterns.extend(companions);
+
+        IndexMatcher { patterns }
+    }
+
+    /// New up the monthly index which the daily indices of a basename for a
+    /// month are compacted into. It is named after the monthly companion of
+    /// the dailies' naming pattern, or in the default ```NAME-YYYY.MM``` form
+    /// if they follow no pattern.
+    ///
+    /// # Parameters
+    ///
+    /// * `daily` - One of the daily indices being compacted
+    ///
+    /// # Returns
+    ///
+    /// * `Index` instance
+    pub fn monthly(&self, daily: &Index) -> Index {
+        let date = daily.date();
+        let mut idx = Index::new_monthly(daily.name(), date.year(), date.month());
+        idx.pattern = daily.pattern.as_ref()
+            .and_then(|format| self.patterns.iter().find(|p| &p.format == format))
+            .and_then(|p| p.monthly())
+            .map(|p| p.format);
+        idx
+    }
+
+    /// Parse an index name
+    ///
+    /// # Parameters
+    ///
+    /// * `input` - The name of the index
+    ///
+    /// # Returns
+    ///
+    /// * `Index` instance if successful
+    /// * `EcError` if the name matches neither a pattern nor the default grammar
+    pub fn parse(&self, input: &str) -> Result<Index, EcError> {
+        self.patterns.iter()
+            .filter_map(|p| p.parse(input))
+            .next()
+            .map(Ok)
+            .unwrap_or_else(|| Index::from_str(input))
+    }
+
+    /// Parse an index as reported by elasticsearch. Data stream backing indices
+    /// are named after their stream. Indices whose names hold no date are
+    /// treated as rollover indices, dated by their creation date, if they end
+    /// in a zero padded six digit generation (eg `-000042`) or an alias points
+    /// at them. Other names, such as `app-20181002` when no pattern matches
+    /// it, are not guessed at, nor are hidden or system indices, whose names
+    /// start with a `.`.
+    ///
+    /// # Parameters
+    ///
+    /// * `raw`     - The index metadata returned by elasticsearch
+    /// * `aliased` - The names of the indices which aliases point at
+    ///
+    /// # Returns
+    ///
+    /// * `Index` instance if successful
+    /// * `EcError` if the index is neither dated nor a rollover index
+    pub fn parse_raw(&self, raw: &RawIndex, aliased: &HashSet<String>) -> Result<Index, EcError> {
+        if raw.index.starts_with(".ds-") {
+            return Index::from_backing(&raw.index, raw.created());
+        }
+        self.parse(&raw.index).or_else(|e| match raw.created() {
+            Some(_) if raw.index.starts_with('.') => Err(e),
+            Some(created) if padded_generation(&raw.index) || aliased.contains(&raw.index) =>
+                Index::from_rollover(&raw.index, created),
+            _ => Err(e),
+        })
+    }
+}
+
+// Whether a name ends in a generation as the rollover api numbers them: a
+// dash and six digits, zero padded
+fn padded_generation(name: &str) -> bool {
+    match name.rfind('-') {
+        Some(dash) => {
+            let generation = &name[dash + 1..];
+            generation.len() == 6 && generation.starts_with('0') && generation.chars().all(|c| c.is_ascii_digit())
+        },
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(format: &str, input: &str) -> Index {
+        let pattern = IndexPattern::new(format, None).unwrap();
+        let mut idx = pattern.parse(input).unwrap();
+        assert_eq!(format!("{}", idx), input);
+        // the pattern alone rebuilds the same name
+        idx.raw = None;
+        assert_eq!(format!("{}", idx), input);
+        idx
+    }
+
+    #[test]
+    fn can_parse_underscore_compact_date() {
+        let idx = round_trip("{base}_%Y%m%d", "foo_20181002");
+        assert_eq!(idx.name(), "foo");
+        assert_eq!(idx.date(), &NaiveDate::from_ymd_opt(2018, 10, 2).unwrap());
+        assert_eq!(idx.granularity, Granularity::Daily);
+    }
+
+    #[test]
+    fn can_parse_dashed_date() {
+        let idx = round_trip("{base}-%Y-%m-%d", "foo-bar-2018-10-02");
+        assert_eq!(idx.name(), "foo-bar");
+    }
+
+    #[test]
+    fn can_parse_date_before_base() {
+        let idx = round_trip("%Y.%m.%d-{base}", "2018.10.02-foo");
+        assert_eq!(idx.name(), "foo");
+    }
+
+    #[test]
+    fn can_parse_hourly_weekly_and_monthly_patterns() {
+        assert_eq!(round_trip("{base}-%Y%m%d%H", "foo-2018100213").granularity, Granularity::Hourly(13));
+        assert_eq!(round_trip("{base}-%G.w%V", "foo-2018.w42").granularity, Granularity::Weekly);
+        assert_eq!(round_trip("{base}_%Y%m", "foo_201810").granularity, Granularity::Monthly);
+    }
+
+    #[test]
+    fn attached_pattern_only_matches_its_base() {
+        let pattern = IndexPattern::new("{base}_%Y%m%d", Some("foo")).unwrap();
+        assert!(pattern.parse("foo_20181002").is_some());
+        assert!(pattern.parse("bar_20181002").is_none());
+    }
+
+    #[test]
+    fn invalid_dates_do_not_match() {
+        let pattern = IndexPattern::new("{base}_%Y%m%d", None).unwrap();
+        assert!(pattern.parse("foo_20180230").is_none());
+    }
+
+    #[test]
+    fn malformed_patterns_produce_errors() {
+        assert!(IndexPattern::new("%Y.%m.%d", None).is_err());
+        assert!(IndexPattern::new("{base}-%Y.%q", None).is_err());
+        assert!(IndexPattern::new("{base}-%Y.%Y", None).is_err());
+        assert!(IndexPattern::new("{base}-%d", None).is_err());
+    }
+
+    #[test]
+    fn daily_patterns_have_monthly_companions() {
+        let monthly = |format: &str| IndexPattern::new(format, None).unwrap().monthly().map(|p| p.format);
+        assert_eq!(monthly("{base}_%Y%m%d"), Some("{base}_%Y%m".to_string()));
+        assert_eq!(monthly("{base}-%Y-%m-%d"), Some("{base}-%Y-%m".to_string()));
+        assert_eq!(monthly("%Y.%m.%d-{base}"), Some("%Y.%m-{base}".to_string()));
+        assert_eq!(monthly("%d.%m.%Y-{base}"), Some("%m.%Y-{base}".to_string()));
+        assert_eq!(monthly("{base}_%Y%m"), None);
+        assert_eq!(monthly("{base}-%Y%m%d%H"), None);
+    }
+
+    #[test]
+    fn compacted_indices_follow_and_parse_back_with_the_daily_pattern() {
+        let mut config = Config::new("localhost", "9200", "60");
+        config.patterns.push("{base}_%Y%m%d".to_string());
+        let matcher = IndexMatcher::new(&config);
+
+        let daily = matcher.parse("foo_20181002").unwrap();
+        let monthly = matcher.monthly(&daily);
+        assert_eq!(monthly.to_string(), "foo_201810");
+        let parsed = matcher.parse("foo_201810").unwrap();
+        assert_eq!(parsed.granularity, Granularity::Monthly);
+        assert_eq!(parsed.name(), "foo");
+
+        let daily = matcher.parse("bar-2018.10.02").unwrap();
+        assert_eq!(matcher.monthly(&daily).to_string(), "bar-2018.10");
+    }
+
+    #[test]
+    fn system_indices_are_not_rollover_indices() {
+        let raw = |index: &str| RawIndex {
+            health: Some("green".to_string()),
+            status: "open".to_string(),
+            index: index.to_string(),
+            pri: None,
+            rep: None,
+            docs_count: None,
+            store_size: None,
+            pri_store_size: None,
+            creation_date: Some("1538438400000".to_string()),
+        };
+        let matcher = IndexMatcher::default();
+        let aliased = HashSet::new();
+        assert_eq!(matcher.parse_raw(&raw("foo-000042"), &aliased).unwrap().granularity, Granularity::Rollover(42));
+        assert!(matcher.parse_raw(&raw(".security-000007"), &aliased).is_err());
+        assert!(matcher.parse_raw(&raw(".kibana_task_manager-000001"), &aliased).is_err());
+        assert_eq!(matcher.parse_raw(&raw(".ds-app-logs-2018.10.02-000001"), &aliased).unwrap().name(), "app-logs");
+    }
+
+    #[test]
+    fn unmatched_dates_are_not_rollover_indices() {
+        let raw = |index: &str| RawIndex {
+            health: Some("green".to_string()),
+            status: "open".to_string(),
+            index: index.to_string(),
+            pri: None,
+            rep: None,
+            docs_count: None,
+            store_size: None,
+            pri_store_size: None,
+            creation_date: Some("1538438400000".to_string()),
+        };
+        let matcher = IndexMatcher {
+            patterns: vec![IndexPattern::new("{base}_%Y%m%d", None).unwrap()],
+        };
+        let aliased = HashSet::new();
+        assert_eq!(matcher.parse_raw(&raw("app_20181002"), &aliased).unwrap().granularity, Granularity::Daily);
+        assert!(matcher.parse_raw(&raw("app-20181002"), &aliased).is_err());
+        assert!(matcher.parse_raw(&raw("metrics-123456"), &aliased).is_err());
+
+        // unless an alias says otherwise
+        let aliased = ["metrics-123456".to_string()].iter().cloned().collect::<HashSet<String>>();
+        assert_eq!(matcher.parse_raw(&raw("metrics-123456"), &aliased).unwrap().granularity, Granularity::Rollover(123456));
+    }
+
+    #[test]
+    fn matcher_falls_back_on_default_grammar() {
+        let matcher = IndexMatcher {
+            patterns: vec![IndexPattern::new("{base}_%Y%m%d", None).unwrap()],
+        };
+        assert_eq!(format!("{}", matcher.parse("foo_20181002").unwrap()), "foo_20181002");
+        assert_eq!(format!("{}", matcher.parse("foo-2018.10.02").unwrap()), "foo-2018.10.02");
+        assert!(matcher.parse("foo.20181002").is_err());
+    }
+}
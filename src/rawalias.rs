@@ -0,0 +1,81 @@
+//! # rawalias.rs
+//!
+//! Herein we define a RawAlias struct which models a row of
+//! elasticsearch's `_cat/aliases` api
+use std::collections::{BTreeMap, HashSet};
+
+/// The RawAlias struct reflects a row of `_cat/aliases` as returned by
+/// Elasticsearch. Versions prior to 6.4 do not report `is_write_index`.
+/// It is designed to be deserialized via serde_json
+#[derive(Deserialize, Debug, Clone)]
+pub struct RawAlias {
+    pub alias: String,
+    pub index: String,
+    pub is_write_index: Option<String>,
+}
+
+/// Return the names of the indices which are the current write index of an
+/// alias. An index is the write index if it is flagged as such, or if it is
+/// the only index behind an alias which flags none.
+///
+/// # Parameters
+///
+/// * `aliases` - The rows of `_cat/aliases`
+///
+/// # Returns
+///
+/// * `HashSet` of index names
+pub fn write_indices(aliases: &[RawAlias]) -> HashSet<String> {
+    let mut by_alias: BTreeMap<&str, Vec<&RawAlias>> = BTreeMap::new();
+    for alias in aliases {
+        by_alias.entry(alias.alias.as_str()).or_default().push(alias);
+    }
+
+    by_alias.values()
+        .flat_map(|rows| {
+            let flagged = rows.iter()
+                .filter(|r| r.is_write_index.as_deref() == Some("true"))
+                .map(|r| r.index.clone())
+                .collect::<Vec<String>>();
+            if flagged.is_empty() && rows.len() == 1 && rows[0].is_write_index.as_deref() != Some("false") {
+                vec![rows[0].index.clone()]
+            } else {
+                flagged
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alias(alias: &str, index: &str, is_write_index: Option<&str>) -> RawAlias {
+        RawAlias {
+            alias: alias.to_string(),
+            index: index.to_string(),
+            is_write_index: is_write_index.map(String::from),
+        }
+    }
+
+    #[test]
+    fn flagged_index_is_write_index() {
+        let aliases = vec![
+            alias("app-logs", "app-logs-000041", Some("false")),
+            alias("app-logs", "app-logs-000042", Some("true")),
+        ];
+        let expected = vec!["app-logs-000042".to_string()].into_iter().collect::<HashSet<String>>();
+        assert_eq!(write_indices(&aliases), expected);
+    }
+
+    #[test]
+    fn sole_unflagged_index_is_write_index() {
+        let aliases = vec![
+            alias("app-logs", "app-logs-000042", None),
+            alias("search", "foo-2018.10.01", Some("-")),
+            alias("search", "foo-2018.10.02", Some("-")),
+        ];
+        let expected = vec!["app-logs-000042".to_string()].into_iter().collect::<HashSet<String>>();
+        assert_eq!(write_indices(&aliases), expected);
+    }
+}
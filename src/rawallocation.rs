@@ -0,0 +1,58 @@
+//! # rawallocation.rs
+//!
+//! Herein we define a RawAllocation struct which models the per node
+//! disk usage returned by elasticsearch's `_cat/allocation` api
+use crate::errors::EcError;
+use crate::size::parse_size;
+
+/// The RawAllocation struct reflects a row of `_cat/allocation`
+/// as returned by Elasticsearch. Shards which are not assigned to a node
+/// show up as a row whose node is `UNASSIGNED` and whose disk fields are null.
+/// It is designed to be deserialized via serde_json
+#[derive(Deserialize, Debug)]
+pub struct RawAllocation {
+    pub shards: String,
+    #[serde(rename = "disk.indices")]
+    pub disk_indices: Option<String>,
+    #[serde(rename = "disk.used")]
+    pub disk_used: Option<String>,
+    #[serde(rename = "disk.avail")]
+    pub disk_avail: Option<String>,
+    #[serde(rename = "disk.total")]
+    pub disk_total: Option<String>,
+    #[serde(rename = "disk.percent")]
+    pub disk_percent: Option<String>,
+    pub host: Option<String>,
+    pub ip: Option<String>,
+    pub node: String,
+}
+
+// parse an optional size field, treating a missing value as zero bytes
+fn bytes(field: &Option<String>) -> Result<u64, EcError> {
+    match field {
+        Some(ref size) => parse_size(size),
+        None => Ok(0),
+    }
+}
+
+impl RawAllocation {
+    /// Whether the row describes a node, as opposed to unassigned shards
+    pub fn is_node(&self) -> bool {
+        self.disk_total.is_some()
+    }
+
+    /// Bytes used by index data on the node
+    pub fn indices_bytes(&self) -> Result<u64, EcError> {
+        bytes(&self.disk_indices)
+    }
+
+    /// Bytes used on the node's disk, by index data and anything else
+    pub fn used_bytes(&self) -> Result<u64, EcError> {
+        bytes(&self.disk_used)
+    }
+
+    /// Total size of the node's disk in bytes
+    pub fn total_bytes(&self) -> Result<u64, EcError> {
+        bytes(&self.disk_total)
+    }
+}
@@ -0,0 +1,56 @@
+//! # rawdatastream.rs
+//!
+//! Herein we define the structs which model the data streams returned by
+//! elasticsearch's `_data_stream` api
+
+/// The outer map returned by `_data_stream`
+#[derive(Deserialize, Debug)]
+pub struct RawDataStreamRoot {
+    pub data_streams: Vec<RawDataStream>,
+}
+
+/// A data stream, along with its backing indices, oldest first.
+/// It is designed to be deserialized via serde_json
+#[derive(Deserialize, Debug)]
+pub struct RawDataStream {
+    pub name: String,
+    pub generation: u32,
+    pub indices: Vec<RawBackingIndex>,
+    pub status: Option<String>,
+}
+
+/// A backing index of a data stream
+#[derive(Deserialize, Debug)]
+pub struct RawBackingIndex {
+    pub index_name: String,
+}
+
+impl RawDataStream {
+    /// The name of the backing index the stream currently writes to, which
+    /// is always the most recent one
+    pub fn write_index(&self) -> Option<&str> {
+        self.indices.last().map(|i| i.index_name.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_index_is_newest_backing_index() {
+        let body = r#"{"data_streams": [{
+            "name": "app-logs",
+            "timestamp_field": {"name": "@timestamp"},
+            "indices": [
+                {"index_name": ".ds-app-logs-2018.10.01-000001", "index_uuid": "a"},
+                {"index_name": ".ds-app-logs-2018.10.02-000002", "index_uuid": "b"}
+            ],
+            "generation": 2,
+            "status": "GREEN",
+            "template": "app-logs"
+        }]}"#;
+        let root: RawDataStreamRoot = serde_json::from_str(body).unwrap();
+        assert_eq!(root.data_streams[0].write_index(), Some(".ds-app-logs-2018.10.02-000002"));
+    }
+}
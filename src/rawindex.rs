@@ -2,19 +2,74 @@
 //!
 //! Herein we define a RawIndex struct which models metadata
 //! returned by elasticsearch when querying the state of an index
+use chrono::{naive::NaiveDate, TimeZone, Utc};
+use crate::errors::EcError;
+use crate::size::parse_size;
 
 /// The RawIndex struct reflects the Index data structure
-/// as returned by Elasticsearch.
+/// as returned by Elasticsearch. Closed indices report no health,
+/// shard counts, or sizes, hence the optional fields.
 /// It is designed to be deserialized via serde_json
 #[derive(Deserialize, Debug)]
 pub struct RawIndex {
-    pub health: String,
+    pub health: Option<String>,
     pub status: String,
     pub index: String,
-    pub pri: String,
-    pub rep: String,
+    pub pri: Option<String>,
+    pub rep: Option<String>,
+    #[serde(rename = "docs.count")]
+    pub docs_count: Option<String>,
     #[serde(rename = "store.size")]
-    pub store_size: String,
+    pub store_size: Option<String>,
     #[serde(rename = "pri.store.size")]
-    pub pri_store_size: String,
+    pub pri_store_size: Option<String>,
+    /// Milliseconds since the epoch at which the index was created
+    #[serde(rename = "creation.date")]
+    pub creation_date: Option<String>,
+}
+
+impl RawIndex {
+    /// Return the total store size of the index, including replicas, in bytes.
+    /// Closed indices, which report no size, count as zero bytes, so they
+    /// should be left out of anything which weighs indices by size.
+    ///
+    /// # Returns
+    ///
+    /// * `u64` number of bytes if successful
+    /// * `EcError` if the reported size could not be parsed
+    pub fn store_bytes(&self) -> Result<u64, EcError> {
+        match self.store_size {
+            Some(ref size) => parse_size(size),
+            None => Ok(0),
+        }
+    }
+
+    /// Return the number of documents in the index. Closed indices, which
+    /// report no count, count as zero documents.
+    ///
+    /// # Returns
+    ///
+    /// * `u64` number of documents if successful
+    /// * `EcError` if the reported count could not be parsed
+    pub fn doc_count(&self) -> Result<u64, EcError> {
+        match self.docs_count {
+            Some(ref count) => count.parse::<u64>()
+                .map_err(|e| EcError::ParseError(format!("docs.count {}: {}", count, e))),
+            None => Ok(0),
+        }
+    }
+
+    /// Whether the index is open, as opposed to closed
+    pub fn is_open(&self) -> bool {
+        self.status == "open"
+    }
+
+    /// The day, in UTC, on which the index was created, if elasticsearch
+    /// reported it
+    pub fn created(&self) -> Option<NaiveDate> {
+        self.creation_date.as_ref()
+            .and_then(|millis| millis.parse::<i64>().ok())
+            .and_then(|millis| Utc.timestamp_millis_opt(millis).single())
+            .map(|dt| dt.date_naive())
+    }
 }
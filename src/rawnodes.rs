@@ -0,0 +1,66 @@
+//! # rawnodes.rs
+//!
+//! Herein we define the structs which model the http addresses of the nodes
+//! in a cluster, as returned by elasticsearch's `_nodes/http` api
+use std::collections::BTreeMap;
+
+/// The outer map returned by `_nodes/http`, keyed by node id
+#[derive(Deserialize, Debug)]
+pub struct RawNodesRoot {
+    pub nodes: BTreeMap<String, RawNode>,
+}
+
+/// A node in the cluster. Nodes with http disabled have no `http` section.
+/// It is designed to be deserialized via serde_json
+#[derive(Deserialize, Debug)]
+pub struct RawNode {
+    pub name: String,
+    pub http: Option<RawNodeHttp>,
+}
+
+/// The http settings of a node
+#[derive(Deserialize, Debug)]
+pub struct RawNodeHttp {
+    pub publish_address: String,
+}
+
+impl RawNode {
+    /// The `host:port` the node serves http requests on, if it does. The
+    /// publish address is either `ip:port`, or `hostname/ip:port` when the
+    /// node was configured by hostname, in which case the hostname is used
+    /// so that it matches the node's certificate.
+    pub fn http_address(&self) -> Option<String> {
+        let address = self.http.as_ref()?.publish_address.as_str();
+        match address.find('/') {
+            Some(slash) => {
+                let (host, ip) = (&address[..slash], &address[slash + 1..]);
+                let port = &ip[ip.rfind(':')? + 1..];
+                Some(format!("{}:{}", host, port))
+            },
+            None => Some(address.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn http_address_prefers_hostname() {
+        let body = r#"{"_nodes": {"total": 3}, "cluster_name": "logs", "nodes": {
+            "a": {"name": "client-01", "http": {"publish_address": "client-01/10.0.0.1:9200"}},
+            "b": {"name": "client-02", "http": {"publish_address": "10.0.0.2:9200"}},
+            "c": {"name": "client-03", "http": {"publish_address": "[::1]:9201"}},
+            "d": {"name": "master-01"}
+        }}"#;
+        let root: RawNodesRoot = serde_json::from_str(body).unwrap();
+        let addresses = root.nodes.values().map(|n| n.http_address()).collect::<Vec<Option<String>>>();
+        assert_eq!(addresses, vec![
+            Some("client-01:9200".to_string()),
+            Some("10.0.0.2:9200".to_string()),
+            Some("[::1]:9201".to_string()),
+            None,
+        ]);
+    }
+}
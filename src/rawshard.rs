@@ -0,0 +1,100 @@
+//! # rawshard.rs
+//!
+//! Herein we define the structs which model the shards and index settings
+//! returned by elasticsearch's `_cat/shards` and `_settings` apis, which
+//! tell whether the lifecycle stages have already been applied to an index
+use std::collections::{BTreeMap, HashSet};
+
+/// The RawShard struct reflects a row of `_cat/shards`. Unassigned shards
+/// report no segment count.
+/// It is designed to be deserialized via serde_json
+#[derive(Deserialize, Debug)]
+pub struct RawShard {
+    pub index: String,
+    pub shard: String,
+    pub prirep: String,
+    #[serde(rename = "segments.count")]
+    pub segments_count: Option<String>,
+}
+
+/// The settings of an index, as returned by `<index>/_settings` with
+/// `flat_settings`
+#[derive(Deserialize, Debug)]
+pub struct RawIndexSettings {
+    #[serde(default)]
+    pub settings: BTreeMap<String, String>,
+}
+
+/// The names of the indices whose assigned shards all hold no more than
+/// `max_num_segments` segments, and so gain nothing from a force merge
+///
+/// # Parameters
+///
+/// * `shards`           - The shards of the indices
+/// * `max_num_segments` - The number of segments a merge would merge down to
+///
+/// # Returns
+///
+/// * `HashSet` of index names
+pub fn merged_indices(shards: &[RawShard], max_num_segments: u32) -> HashSet<String> {
+    let mut merged = shards.iter()
+        .filter(|s| s.segments_count.is_some())
+        .map(|s| s.index.clone())
+        .collect::<HashSet<String>>();
+    for shard in shards {
+        let count = shard.segments_count.as_ref().and_then(|c| c.parse::<u32>().ok());
+        if count.map(|c| c > max_num_segments).unwrap_or(false) {
+            merged.remove(&shard.index);
+        }
+    }
+    merged
+}
+
+/// The names of the indices which already block writes
+///
+/// # Parameters
+///
+/// * `settings` - The settings of the indices, keyed by index name
+///
+/// # Returns
+///
+/// * `HashSet` of index names
+pub fn write_blocked(settings: &BTreeMap<String, RawIndexSettings>) -> HashSet<String> {
+    settings.iter()
+        .filter(|(_, s)| s.settings.get("index.blocks.write").map(|v| v == "true").unwrap_or(false))
+        .map(|(index, _)| index.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indices_are_merged_once_every_shard_is() {
+        let body = r#"[
+            {"index": "foo-2018.10.01", "shard": "0", "prirep": "p", "segments.count": "1"},
+            {"index": "foo-2018.10.01", "shard": "0", "prirep": "r", "segments.count": "1"},
+            {"index": "foo-2018.10.02", "shard": "0", "prirep": "p", "segments.count": "1"},
+            {"index": "foo-2018.10.02", "shard": "0", "prirep": "r", "segments.count": "7"},
+            {"index": "foo-2018.10.03", "shard": "0", "prirep": "p", "segments.count": "1"},
+            {"index": "foo-2018.10.03", "shard": "0", "prirep": "r", "segments.count": null}
+        ]"#;
+        let shards: Vec<RawShard> = serde_json::from_str(body).unwrap();
+        let mut merged = merged_indices(&shards, 1).into_iter().collect::<Vec<String>>();
+        merged.sort();
+        assert_eq!(merged, vec!["foo-2018.10.01", "foo-2018.10.03"]);
+        assert_eq!(merged_indices(&shards, 10).len(), 3);
+    }
+
+    #[test]
+    fn finds_write_blocked_indices() {
+        let body = r#"{
+            "foo-2018.10.01": {"settings": {"index.blocks.write": "true"}},
+            "foo-2018.10.02": {"settings": {"index.blocks.write": "false"}},
+            "foo-2018.10.03": {"settings": {}}
+        }"#;
+        let settings: BTreeMap<String, RawIndexSettings> = serde_json::from_str(body).unwrap();
+        assert_eq!(write_blocked(&settings).into_iter().collect::<Vec<String>>(), vec!["foo-2018.10.01"]);
+    }
+}
@@ -0,0 +1,116 @@
+//! # rawsnapshot.rs
+//!
+//! Herein we define structs which model the snapshot metadata returned
+//! by elasticsearch's `_snapshot` apis
+use std::collections::HashMap;
+
+/// The outer map returned by `_snapshot/<repo>/_all`
+#[derive(Deserialize, Debug)]
+pub struct RawSnapshotRoot {
+    pub snapshots: Vec<RawSnapshot>,
+}
+
+/// A snapshot in a repository, as listed by `_snapshot/<repo>/_all`
+#[derive(Deserialize, Debug)]
+pub struct RawSnapshot {
+    pub snapshot: String,
+    pub state: String,
+    #[serde(default)]
+    pub indices: Vec<String>,
+    pub start_time_in_millis: Option<i64>,
+}
+
+/// The outer map returned by `_snapshot/<repo>/<snapshot>/_status`
+#[derive(Deserialize, Debug)]
+pub struct RawSnapshotStatusRoot {
+    pub snapshots: Vec<RawSnapshotStatus>,
+}
+
+/// The status of a single snapshot. The state is one of `IN_PROGRESS`,
+/// `STARTED`, `SUCCESS`, `FAILED`, `ABORTED` or `PARTIAL`.
+#[derive(Deserialize, Debug)]
+pub struct RawSnapshotStatus {
+    pub snapshot: String,
+    pub repository: String,
+    pub state: String,
+    #[serde(default)]
+    pub indices: HashMap<String, RawSnapshotIndexStatus>,
+}
+
+/// The status of one index within a snapshot
+#[derive(Deserialize, Debug)]
+pub struct RawSnapshotIndexStatus {
+    pub shards_stats: RawShardsStats,
+}
+
+/// Shard counts of an index within a snapshot
+#[derive(Deserialize, Debug)]
+pub struct RawShardsStats {
+    pub done: u32,
+    pub failed: u32,
+    pub total: u32,
+}
+
+impl RawSnapshotStatus {
+    /// Whether elasticsearch is still working on the snapshot
+    pub fn in_progress(&self) -> bool {
+        self.state == "IN_PROGRESS" || self.state == "STARTED"
+    }
+
+    /// Whether the snapshot completed successfully
+    pub fn succeeded(&self) -> bool {
+        self.state == "SUCCESS"
+    }
+
+    /// Whether every shard of the named index was snapshotted
+    pub fn index_succeeded(&self, index: &str) -> bool {
+        match self.indices.get(index) {
+            Some(status) => {
+                let stats = &status.shards_stats;
+                stats.failed == 0 && stats.done == stats.total
+            },
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(state: &str) -> RawSnapshotStatus {
+        let body = format!(r#"{{"snapshots": [{{
+            "snapshot": "elasticlean-foo-2018.10.20-01.00.00",
+            "repository": "backups",
+            "uuid": "abc",
+            "state": "{}",
+            "include_global_state": false,
+            "shards_stats": {{"initializing": 0, "started": 0, "finalizing": 0, "done": 3, "failed": 0, "total": 3}},
+            "indices": {{
+                "foo-2018.10.01": {{"shards_stats": {{"done": 2, "failed": 0, "total": 2}}}},
+                "foo-2018.10.02": {{"shards_stats": {{"done": 0, "failed": 1, "total": 1}}}}
+            }}
+        }}]}}"#, state);
+        let root: RawSnapshotStatusRoot = serde_json::from_str(&body).unwrap();
+        root.snapshots.into_iter().next().unwrap()
+    }
+
+    #[test]
+    fn parses_snapshot_states() {
+        assert!(status("SUCCESS").succeeded());
+        assert!(!status("SUCCESS").in_progress());
+        for state in &["IN_PROGRESS", "STARTED"] {
+            assert!(status(state).in_progress());
+            assert!(!status(state).succeeded());
+        }
+        assert!(!status("PARTIAL").succeeded());
+    }
+
+    #[test]
+    fn index_succeeds_only_when_every_shard_is_done() {
+        let status = status("PARTIAL");
+        assert!(status.index_succeeded("foo-2018.10.01"));
+        assert!(!status.index_succeeded("foo-2018.10.02"));
+        assert!(!status.index_succeeded("foo-2018.10.03"));
+    }
+}
@@ -0,0 +1,37 @@
+//! # rawtask.rs
+//!
+//! Herein we define a RawTask struct which models the state of a long
+//! running task (such as a reindex) returned by elasticsearch's `_tasks` api
+use serde_json::Value;
+
+/// The state of a task, as returned by `_tasks/<task id>`. Once the task
+/// has completed, either `response` or `error` is filled in.
+#[derive(Deserialize, Debug)]
+pub struct RawTask {
+    pub completed: bool,
+    pub response: Option<Value>,
+    pub error: Option<Value>,
+}
+
+/// The reply to a request which was started as a task
+#[derive(Deserialize, Debug)]
+pub struct RawTaskId {
+    pub task: String,
+}
+
+impl RawTask {
+    /// Return a description of the reason the task failed, if it did. This
+    /// covers both outright errors and the per document `failures` reported
+    /// by reindex.
+    pub fn failure(&self) -> Option<String> {
+        if let Some(ref error) = self.error {
+            return Some(error.to_string());
+        }
+        match self.response.as_ref().and_then(|r| r.get("failures")) {
+            Some(Value::Array(failures)) if !failures.is_empty() => {
+                Some(Value::Array(failures.clone()).to_string())
+            },
+            _ => None,
+        }
+    }
+}
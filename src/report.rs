@@ -0,0 +1,526 @@
+//! # report.rs
+//!
+//! Machine readable renderings of index listings, for the `--format`
+//! option of query, process and delete. Each index becomes an `IndexRow`,
+//! and a listing is summed up by a `Summary`, or rolled up by basename
+//! into `BasenameSummary`s.
+use chrono::{naive::NaiveDate, Duration};
+use crate::audit::Finding;
+use crate::errors::EcError;
+use crate::index::{Granularity, Index};
+use crate::rawindex::RawIndex;
+use crate::size::format_size;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+
+/// The shape of the output of a command
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// The free form text each command has always printed
+    #[default]
+    Text,
+    /// An aligned table with a header and a summary line
+    Table,
+    /// A single json object holding the indices and a summary
+    Json,
+    /// A header and one line per index; csv has no room for a summary
+    Csv,
+}
+
+impl FromStr for OutputFormat {
+    type Err = EcError;
+
+    fn from_str(input: &str) -> Result<OutputFormat, EcError> {
+        match input.to_lowercase().as_str() {
+            "text"  => Ok(OutputFormat::Text),
+            "table" => Ok(OutputFormat::Table),
+            "json"  => Ok(OutputFormat::Json),
+            "csv"   => Ok(OutputFormat::Csv),
+            _ => Err(EcError::InvalidArgument(
+                format!("unknown format {}. expected text, table, json or csv", input))),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            OutputFormat::Text  => "text",
+            OutputFormat::Table => "table",
+            OutputFormat::Json  => "json",
+            OutputFormat::Csv   => "csv",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A single index, along with the metadata elasticsearch reports for it.
+/// Closed indices report no health, document count or size.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct IndexRow {
+    pub index: String,
+    pub basename: String,
+    /// The first day the index covers, as `YYYY-MM-DD`
+    pub date: String,
+    pub age_days: i64,
+    pub health: Option<String>,
+    pub status: String,
+    pub docs_count: Option<u64>,
+    /// The store size, including replicas, as elasticsearch reported it
+    pub store_size: Option<String>,
+    pub store_bytes: Option<u64>,
+}
+
+impl IndexRow {
+    /// Build a row from an index and its metadata.
+    ///
+    /// # Parameters
+    ///
+    /// * `idx` - The index
+    /// * `raw` - The metadata elasticsearch returned for the index
+    /// * `age` - The age of the index in days
+    ///
+    /// # Returns
+    ///
+    /// * `IndexRow` if successful
+    /// * `EcError` if the reported count or size could not be parsed
+    pub fn new(idx: &Index, raw: &RawIndex, age: i64) -> Result<IndexRow, EcError> {
+        Ok(IndexRow {
+            index: raw.index.clone(),
+            basename: idx.name().to_string(),
+            date: idx.date().format("%Y-%m-%d").to_string(),
+            age_days: age,
+            health: raw.health.clone(),
+            status: raw.status.clone(),
+            docs_count: match raw.docs_count { Some(_) => Some(raw.doc_count()?), None => None },
+            store_size: raw.store_size.clone(),
+            store_bytes: match raw.store_size { Some(_) => Some(raw.store_bytes()?), None => None },
+        })
+    }
+}
+
+/// The totals of a listing of indices
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct Summary {
+    pub indices: usize,
+    pub docs_count: u64,
+    pub store_bytes: u64,
+}
+
+impl Summary {
+    /// Total up a listing of indices
+    pub fn of(rows: &[IndexRow]) -> Summary {
+        Summary {
+            indices: rows.len(),
+            docs_count: rows.iter().filter_map(|r| r.docs_count).sum(),
+            store_bytes: rows.iter().filter_map(|r| r.store_bytes).sum(),
+        }
+    }
+}
+
+/// A run of consecutive days, within the dates of a basename, which no
+/// index covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Gap {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+}
+
+impl Gap {
+    /// The number of days missing
+    pub fn days(&self) -> i64 {
+        (self.to - self.from).num_days() + 1
+    }
+}
+
+impl fmt::Display for Gap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.from == self.to {
+            write!(f, "{}", self.from)
+        } else {
+            write!(f, "{}..{}", self.from, self.to)
+        }
+    }
+}
+
+/// Find the days between the first and last day covered by a set of indices
+/// which none of them cover. An hourly index counts as covering its whole
+/// day. Rollover indices hold data until they are rolled over, so they
+/// cover no particular days and are ignored.
+///
+/// # Parameters
+///
+/// * `indices` - The indices of a single basename, in any order
+///
+/// # Returns
+///
+/// * `Vector` of `Gap`s, oldest first
+pub fn gaps(indices: &[Index]) -> Vec<Gap> {
+    let mut covered = BTreeSet::new();
+    for idx in indices {
+        if let Granularity::Rollover(_) = idx.granularity {
+            continue;
+        }
+        let mut day = idx.date;
+        while day <= idx.last_date() {
+            covered.insert(day);
+            day += Duration::days(1);
+        }
+    }
+
+    let mut gaps = Vec::new();
+    let mut days = covered.iter();
+    let mut prev = match days.next() {
+        Some(day) => *day,
+        None => return gaps,
+    };
+    for day in days {
+        if *day - prev > Duration::days(1) {
+            gaps.push(Gap { from: prev + Duration::days(1), to: *day - Duration::days(1) });
+        }
+        prev = *day;
+    }
+    gaps
+}
+
+// Rank index health from best to worst. Closed indices report none.
+fn health_rank(health: &str) -> u8 {
+    match health {
+        "green" => 0,
+        "yellow" => 1,
+        "red" => 2,
+        _ => 3,
+    }
+}
+
+/// The rollup of the indices sharing a basename
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BasenameSummary {
+    pub basename: String,
+    pub indices: usize,
+    /// The first day covered by the oldest index
+    pub oldest: NaiveDate,
+    /// The last day covered by the newest index
+    pub newest: NaiveDate,
+    pub gaps: Vec<Gap>,
+    pub missing_days: i64,
+    pub docs_count: u64,
+    pub store_bytes: u64,
+    /// The worst health of the open indices, or none if all are closed
+    pub worst_health: Option<String>,
+}
+
+impl BasenameSummary {
+    /// Roll up the indices of a basename.
+    ///
+    /// # Parameters
+    ///
+    /// * `basename` - The basename the indices share
+    /// * `indices`  - The indices and their metadata; there must be at least one
+    ///
+    /// # Returns
+    ///
+    /// * `BasenameSummary` if successful
+    /// * `EcError` if there are no indices, or a reported count or size
+    ///   could not be parsed
+    pub fn of(basename: &str, indices: &[(Index, RawIndex)]) -> Result<BasenameSummary, EcError> {
+        let oldest = indices.iter().map(|(idx, _)| idx.date).min()
+            .ok_or_else(|| EcError::InvalidArgument(format!("no {} indices to summarize", basename)))?;
+        let newest = indices.iter().map(|(idx, _)| idx.last_date()).max().unwrap_or(oldest);
+
+        let mut docs_count = 0;
+        let mut store_bytes = 0;
+        for (_, raw) in indices {
+            docs_count += raw.doc_count()?;
+            store_bytes += raw.store_bytes()?;
+        }
+
+        let gaps = gaps(&indices.iter().map(|(idx, _)| idx.clone()).collect::<Vec<Index>>());
+        Ok(BasenameSummary {
+            basename: basename.to_string(),
+            indices: indices.len(),
+            oldest,
+            newest,
+            missing_days: gaps.iter().map(Gap::days).sum(),
+            gaps,
+            docs_count,
+            store_bytes,
+            worst_health: indices.iter()
+                .filter_map(|(_, raw)| raw.health.clone())
+                .max_by_key(|health| health_rank(health)),
+        })
+    }
+}
+
+const HEADER: [&str; 8] = ["index", "basename", "date", "age_days", "health", "status", "docs_count", "store_size"];
+
+const SUMMARY_HEADER: [&str; 8] = ["basename", "indices", "oldest", "newest", "missing_days", "docs_count", "store_size", "worst_health"];
+
+// the fields of a row, in the order of HEADER. Missing values are empty.
+fn fields(row: &IndexRow) -> Vec<String> {
+    vec![
+        row.index.clone(),
+        row.basename.clone(),
+        row.date.clone(),
+        row.age_days.to_string(),
+        row.health.clone().unwrap_or_default(),
+        row.status.clone(),
+        row.docs_count.map(|c| c.to_string()).unwrap_or_default(),
+        row.store_size.clone().unwrap_or_default(),
+    ]
+}
+
+// the fields of a basename summary, in the order of SUMMARY_HEADER
+fn summary_fields(summary: &BasenameSummary) -> Vec<String> {
+    vec![
+        summary.basename.clone(),
+        summary.indices.to_string(),
+        summary.oldest.to_string(),
+        summary.newest.to_string(),
+        summary.missing_days.to_string(),
+        summary.docs_count.to_string(),
+        format_size(summary.store_bytes),
+        summary.worst_health.clone().unwrap_or_default(),
+    ]
+}
+
+// quote a csv field if it holds a delimiter, quote or line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+// render a header and lines of fields as csv
+fn csv(header: &[&str], lines: Vec<Vec<String>>) -> String {
+    let mut out = header.join(",");
+    out.push('\n');
+    for fields in lines {
+        out.push_str(&fields.iter().map(|f| csv_field(f)).collect::<Vec<String>>().join(","));
+        out.push('\n');
+    }
+    out
+}
+
+// render a header and lines of fields as a table, each column as wide as
+// its widest value
+fn table(header: &[&str], lines: Vec<Vec<String>>) -> String {
+    let mut widths = header.iter().map(|h| h.len()).collect::<Vec<usize>>();
+    for fields in &lines {
+        for (width, field) in widths.iter_mut().zip(fields.iter()) {
+            *width = (*width).max(field.len());
+        }
+    }
+
+    let line = |fields: Vec<&str>| {
+        fields.iter()
+            .zip(widths.iter())
+            .map(|(f, w)| format!("{:<width$}", f, width = w))
+            .collect::<Vec<String>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+
+    let mut out = line(header.to_vec());
+    out.push('\n');
+    for fields in &lines {
+        out.push_str(&line(fields.iter().map(String::as_str).collect()));
+        out.push('\n');
+    }
+    out
+}
+
+/// Render rows as csv, with a header line. The store size is given in
+/// bytes, so that it may be summed.
+pub fn to_csv(rows: &[IndexRow]) -> String {
+    let header = HEADER.iter()
+        .map(|h| if *h == "store_size" { "store_bytes" } else { *h })
+        .collect::<Vec<&str>>();
+    csv(&header, rows.iter()
+        .map(|row| {
+            let mut fields = fields(row);
+            fields[7] = row.store_bytes.map(|b| b.to_string()).unwrap_or_default();
+            fields
+        })
+        .collect())
+}
+
+/// Render rows as a table with a header, each column as wide as its widest
+/// value.
+pub fn to_table(rows: &[IndexRow]) -> String {
+    table(&HEADER, rows.iter().map(fields).collect())
+}
+
+/// Render basename summaries as csv, with a header line. The store size
+/// is given in bytes, and the gaps are left to the json output.
+pub fn summaries_to_csv(summaries: &[BasenameSummary]) -> String {
+    let header = SUMMARY_HEADER.iter()
+        .map(|h| if *h == "store_size" { "store_bytes" } else { *h })
+        .collect::<Vec<&str>>();
+    csv(&header, summaries.iter()
+        .map(|summary| {
+            let mut fields = summary_fields(summary);
+            fields[6] = summary.store_bytes.to_string();
+            fields
+        })
+        .collect())
+}
+
+/// Render basename summaries as a table with a header
+pub fn summaries_to_table(summaries: &[BasenameSummary]) -> String {
+    table(&SUMMARY_HEADER, summaries.iter().map(summary_fields).collect())
+}
+
+/// Render audit findings as csv, with a header line. Each finding spans the
+/// days from `from` to `to`, and names the indices involved, if any,
+/// separated by spaces.
+pub fn findings_to_csv(findings: &[Finding]) -> String {
+    csv(&["kind", "basename", "from", "to", "indices"], findings.iter()
+        .map(|finding| match finding {
+            Finding::Missing { basename, from, to, .. } =>
+                vec!["missing".to_string(), basename.clone(), from.to_string(), to.to_string(), String::new()],
+            Finding::Future { basename, index, date } =>
+                vec!["future".to_string(), basename.clone(), date.to_string(), date.to_string(), index.clone()],
+            Finding::Duplicate { basename, date, indices } =>
+                vec!["duplicate".to_string(), basename.clone(), date.to_string(), date.to_string(), indices.join(" ")],
+        })
+        .collect())
+}
+
+/// Render a value as pretty printed json
+pub fn to_json<T: Serialize>(value: &T) -> Result<String, EcError> {
+    serde_json::to_string_pretty(value).map_err(|e| EcError::OutputError(format!("{}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(index: &str, docs: Option<u64>, bytes: Option<u64>) -> IndexRow {
+        IndexRow {
+            index: index.to_string(),
+            basename: "logs".to_string(),
+            date: "2018-10-02".to_string(),
+            age_days: 3,
+            health: docs.map(|_| "green".to_string()),
+            status: if docs.is_some() { "open" } else { "close" }.to_string(),
+            docs_count: docs,
+            store_size: bytes.map(|b| format!("{}b", b)),
+            store_bytes: bytes,
+        }
+    }
+
+    #[test]
+    fn parses_formats() {
+        assert_eq!("JSON".parse::<OutputFormat>().unwrap(), OutputFormat::Json);
+        assert_eq!("csv".parse::<OutputFormat>().unwrap(), OutputFormat::Csv);
+        assert_eq!(OutputFormat::default(), OutputFormat::Text);
+        assert!("yaml".parse::<OutputFormat>().is_err());
+    }
+
+    #[test]
+    fn summary_skips_closed_indices() {
+        let rows = vec![row("logs-2018.10.02", Some(10), Some(100)), row("logs-2018.10.01", None, None)];
+        assert_eq!(Summary::of(&rows), Summary { indices: 2, docs_count: 10, store_bytes: 100 });
+    }
+
+    #[test]
+    fn csv_gives_bytes_and_quotes_fields() {
+        let mut open = row("logs-2018.10.02", Some(10), Some(100));
+        open.basename = "a,b".to_string();
+        let csv = to_csv(&[open, row("logs-2018.10.01", None, None)]);
+        assert_eq!(csv,
+            "index,basename,date,age_days,health,status,docs_count,store_bytes\n\
+             logs-2018.10.02,\"a,b\",2018-10-02,3,green,open,10,100\n\
+             logs-2018.10.01,logs,2018-10-02,3,,close,,\n");
+    }
+
+    #[test]
+    fn findings_csv_quotes_fields() {
+        let date = NaiveDate::from_ymd_opt(2018, 10, 2).unwrap();
+        let findings = vec![
+            Finding::Missing { basename: "a,b".to_string(), from: date, to: date, days: 1 },
+            Finding::Duplicate { basename: "logs".to_string(), date,
+                indices: vec!["logs-2018.10.02".to_string(), "logs-1.0-2018.10.02".to_string()] },
+        ];
+        assert_eq!(findings_to_csv(&findings),
+            "kind,basename,from,to,indices\n\
+             missing,\"a,b\",2018-10-02,2018-10-02,\n\
+             duplicate,logs,2018-10-02,2018-10-02,logs-2018.10.02 logs-1.0-2018.10.02\n");
+    }
+
+    fn raw(index: &str, health: Option<&str>, docs: &str, size: &str) -> RawIndex {
+        serde_json::from_value(json!({
+            "health": health, "status": "open", "index": index,
+            "docs.count": docs, "store.size": size,
+        })).unwrap()
+    }
+
+    #[test]
+    fn gaps_cover_missing_days() {
+        let indices = vec![
+            Index::new("logs", 2018, 10, 1),
+            Index::new("logs", 2018, 10, 5),
+            Index::new("logs", 2018, 10, 3),
+            Index::new("logs", 2018, 10, 6),
+        ];
+        let found = gaps(&indices);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].to_string(), "2018-10-02");
+        assert_eq!(found[1].to_string(), "2018-10-04");
+        assert!(gaps(&[]).is_empty());
+    }
+
+    #[test]
+    fn gaps_respect_granularity() {
+        let indices = vec![
+            Index::new_monthly("logs", 2018, 9),
+            Index::new("logs", 2018, 10, 1),
+            Index::new("logs", 2018, 10, 4),
+        ];
+        let found = gaps(&indices);
+        assert_eq!(found, vec![Gap {
+            from: NaiveDate::from_ymd_opt(2018, 10, 2).unwrap(),
+            to: NaiveDate::from_ymd_opt(2018, 10, 3).unwrap(),
+        }]);
+        assert_eq!(found[0].days(), 2);
+        assert_eq!(found[0].to_string(), "2018-10-02..2018-10-03");
+    }
+
+    #[test]
+    fn summarizes_a_basename() {
+        let indices = vec![
+            (Index::new("logs", 2018, 10, 1), raw("logs-2018.10.01", Some("green"), "10", "1kb")),
+            (Index::new("logs", 2018, 10, 3), raw("logs-2018.10.03", Some("yellow"), "5", "1kb")),
+            (Index::new("logs", 2018, 10, 4), raw("logs-2018.10.04", None, "0", "0b")),
+        ];
+        let summary = BasenameSummary::of("logs", &indices).unwrap();
+        assert_eq!(summary.indices, 3);
+        assert_eq!(summary.oldest.to_string(), "2018-10-01");
+        assert_eq!(summary.newest.to_string(), "2018-10-04");
+        assert_eq!(summary.missing_days, 1);
+        assert_eq!(summary.docs_count, 15);
+        assert_eq!(summary.store_bytes, 2048);
+        assert_eq!(summary.worst_health.as_deref(), Some("yellow"));
+        assert!(BasenameSummary::of("logs", &[]).is_err());
+
+        let table = summaries_to_table(&[summary]);
+        let line = table.lines().nth(1).unwrap();
+        assert!(line.contains(" 2.0kb "));
+        assert!(line.ends_with("yellow"));
+    }
+
+    #[test]
+    fn table_aligns_columns() {
+        let table = to_table(&[row("logs-2018.10.02", Some(10), Some(100))]);
+        let lines = table.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].find("basename"), lines[1].find("logs "));
+        assert!(lines[1].ends_with("100b"));
+    }
+}
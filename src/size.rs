@@ -0,0 +1,101 @@
+//! # size.rs
+//!
+//! Parsing of byte sizes as reported by elasticsearch's _cat apis
+//! (eg `1.2gb`, `345kb`, `12b`) and as supplied on the command line,
+//! and formatting of byte counts in the same style.
+
+use crate::errors::EcError;
+
+// unit suffixes, longest first so that `b` does not shadow `kb` and friends.
+// Elasticsearch uses binary multiples.
+const UNITS: [(&str, u64); 6] = [
+    ("pb", 1 << 50),
+    ("tb", 1 << 40),
+    ("gb", 1 << 30),
+    ("mb", 1 << 20),
+    ("kb", 1 << 10),
+    ("b",  1),
+];
+
+/// Parse a human readable size into a number of bytes. The unit is case
+/// insensitive, and a bare number is taken to be in bytes.
+///
+/// # Parameters
+///
+/// * `input` - A size such as `500gb`, `1.5tb` or `1024`
+///
+/// # Returns
+///
+/// * `u64` number of bytes if successful
+/// * `EcError` if unsuccessful
+pub fn parse_size(input: &str) -> Result<u64, EcError> {
+    let lower = input.trim().to_lowercase();
+    let (number, multiplier) = UNITS.iter()
+        .find(|(unit, _)| lower.ends_with(unit))
+        .map(|(unit, multiplier)| (&lower[..lower.len() - unit.len()], *multiplier))
+        .unwrap_or((lower.as_str(), 1));
+
+    let number = number.trim().parse::<f64>()
+        .map_err(|e| EcError::ParseError(format!("invalid size {}: {}", input, e)))?;
+
+    if number < 0.0 || !number.is_finite() {
+        return Err(EcError::ParseError(format!("invalid size {}", input)));
+    }
+
+    Ok((number * multiplier as f64).round() as u64)
+}
+
+/// Format a number of bytes in the largest unit it fills, with one decimal
+/// place, the way elasticsearch's _cat apis do (eg `1.2gb`).
+///
+/// # Parameters
+///
+/// * `bytes` - The number of bytes
+///
+/// # Returns
+///
+/// * `String` such as `1.2gb`, or `12b` for less than a kilobyte
+pub fn format_size(bytes: u64) -> String {
+    match UNITS.iter().find(|(_, multiplier)| bytes >= *multiplier && *multiplier > 1) {
+        Some((unit, multiplier)) => format!("{:.1}{}", bytes as f64 / *multiplier as f64, unit),
+        None => format!("{}b", bytes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_bare_bytes() {
+        assert_eq!(parse_size("1024"), Ok(1024));
+        assert_eq!(parse_size("12b"), Ok(12));
+    }
+
+    #[test]
+    fn can_parse_units() {
+        assert_eq!(parse_size("2kb"), Ok(2048));
+        assert_eq!(parse_size("500gb"), Ok(500 * (1 << 30)));
+        assert_eq!(parse_size("1TB"), Ok(1 << 40));
+    }
+
+    #[test]
+    fn can_parse_fractional_sizes() {
+        assert_eq!(parse_size("1.5mb"), Ok(1572864));
+    }
+
+    #[test]
+    fn garbage_produces_error() {
+        assert!(parse_size("lots").is_err());
+        assert!(parse_size("-1gb").is_err());
+        assert!(parse_size("").is_err());
+    }
+
+    #[test]
+    fn can_format_sizes() {
+        assert_eq!(format_size(12), "12b");
+        assert_eq!(format_size(2048), "2.0kb");
+        assert_eq!(format_size(1572864), "1.5mb");
+        assert_eq!(parse_size(&format_size(500 * (1 << 30))), Ok(500 * (1 << 30)));
+    }
+}
@@ -1,9 +1,11 @@
 use serde::de::DeserializeOwned;
+use serde::Serialize;
 use std::fmt::Display;
 
 /// Trait which must be implemented by an index struct
 /// in order to work with Elasticlean. This simple trait defines
-/// the base name of the index as a constant.
-pub trait ElasticIndex: DeserializeOwned + Display {
+/// the base name of the index as a constant. Documents are serialized back
+/// out for the json output of the process command.
+pub trait ElasticIndex: DeserializeOwned + Serialize + Display {
     const NAME: &'static str; // the index name
 }
\ No newline at end of file